@@ -0,0 +1,262 @@
+//! Compare the `CacheService` response future with the original boxed
+//! `async move` implementation it replaced (i.e. the cost of the allocation
+//! the hand written future avoids).
+//!
+//! Run with `cargo bench`.
+
+use std::collections::hash_map::DefaultHasher;
+use std::convert::Infallible;
+use std::hash::Hasher;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use axum::{
+    body::{self, Body},
+    headers::{ETag, HeaderMapExt, IfModifiedSince, IfNoneMatch, LastModified},
+    http::{header, Method, Request, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use futures_util::future::BoxFuture;
+use tower::{service_fn, Layer, Service, ServiceExt};
+
+#[macro_use]
+extern crate tracing;
+
+// the crate is a binary so include the module directly
+#[allow(dead_code, unused_imports)]
+#[path = "../src"]
+mod site {
+    pub mod caching;
+}
+
+use site::caching::{CacheControl, CacheLayer, Options};
+
+/// A page similar in size to the pages served by the site.
+const PAGE: &str = include_str!("../templates/pages/projects.html");
+
+async fn handler(_: Request<Body>) -> Result<Response, Infallible> {
+    Ok(Html(PAGE).into_response())
+}
+
+fn bench_cache_service(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("cache_service");
+
+    for etag in [false, true] {
+        let layer = CacheLayer::new(Options {
+            etag,
+            ..Default::default()
+        });
+        let boxed = BoxedCacheService {
+            inner: service_fn(handler),
+            options: layer.options.clone(),
+            errors: layer.errors.clone(),
+        };
+        let service = layer.layer(service_fn(handler));
+
+        group.bench_with_input(
+            BenchmarkId::new("unboxed", etag_label(etag)),
+            &service,
+            |b, service| {
+                b.to_async(&runtime).iter(|| call(service.clone()));
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("boxed", etag_label(etag)),
+            &boxed,
+            |b, service| {
+                b.to_async(&runtime).iter(|| call(service.clone()));
+            },
+        );
+    }
+
+    group.finish();
+}
+
+async fn call<S>(service: S) -> Response
+where
+    S: Service<Request<Body>, Response = Response, Error = Infallible>,
+{
+    let request = Request::get("/").body(Body::empty()).unwrap();
+    service.oneshot(request).await.unwrap()
+}
+
+/// A copy of the `CacheService` before the boxed future was replaced, so the
+/// benchmark keeps comparing against the original implementation.
+///
+/// Policies are left out since the benchmark doesn't add any.
+#[derive(Clone)]
+struct BoxedCacheService<S> {
+    inner: S,
+    options: Options,
+    errors: Options,
+}
+
+impl<S> Service<Request<Body>> for BoxedCacheService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let conditions = Conditions::from_request(&request);
+        let options = self.options.clone();
+        let errors = self.errors.clone();
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let mut response: Response = future.await?;
+
+            let status = response.status();
+            let options =
+                if status.is_client_error() || status.is_server_error() {
+                    errors
+                } else {
+                    options
+                };
+
+            // merge with the existing header (if any) so that the
+            // directives set by the handler take precedence
+            match response.headers().typed_try_get::<CacheControl>() {
+                Ok(existing) => {
+                    let cache_control = existing
+                        .unwrap_or_default()
+                        .merge(&options.cache_control);
+                    trace!("cache-control header created: {}", cache_control);
+                    response.headers_mut().typed_insert(cache_control);
+                }
+                Err(e) => {
+                    let cc = response.headers().get(header::CACHE_CONTROL);
+                    warn!("invalid cache-control header {cc:?}: {e}");
+                }
+            }
+
+            if options.etag && conditions.cacheable(&response) {
+                response = set_etag(response).await;
+            }
+
+            if conditions.not_modified(&response) {
+                debug!("resource not modified");
+                return Ok(not_modified(response));
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+/// The conditional request headers used to decide if a `304 Not Modified`
+/// response can be sent.
+struct Conditions {
+    /// Only `GET` and `HEAD` requests are conditional.
+    safe_method: bool,
+    if_none_match: Option<IfNoneMatch>,
+    if_modified_since: Option<IfModifiedSince>,
+}
+
+impl Conditions {
+    fn from_request(request: &Request<Body>) -> Self {
+        let headers = request.headers();
+        Self {
+            safe_method: matches!(
+                *request.method(),
+                Method::GET | Method::HEAD
+            ),
+            if_none_match: headers.typed_get(),
+            if_modified_since: headers.typed_get(),
+        }
+    }
+
+    /// Check if the response can be used for validation (i.e. it is a
+    /// successful response to a `GET` or `HEAD` request).
+    fn cacheable(&self, response: &Response) -> bool {
+        self.safe_method && response.status() == StatusCode::OK
+    }
+
+    /// Check if the client already has the current version of the response.
+    fn not_modified(&self, response: &Response) -> bool {
+        if !self.cacheable(response) {
+            return false;
+        }
+
+        let headers = response.headers();
+
+        if let Some(if_none_match) = &self.if_none_match {
+            return headers
+                .typed_get::<ETag>()
+                .is_some_and(|etag| !if_none_match.precondition_passes(&etag));
+        }
+
+        if let Some(if_modified_since) = &self.if_modified_since {
+            return headers.typed_get::<LastModified>().is_some_and(|lm| {
+                !if_modified_since.is_modified(SystemTime::from(lm))
+            });
+        }
+
+        false
+    }
+}
+
+/// Buffer the response body and set a strong `ETag` from the hash of the body.
+async fn set_etag(response: Response) -> Response {
+    let is_event_stream = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|ct| ct.as_bytes().starts_with(b"text/event-stream"));
+
+    if is_event_stream || response.headers().contains_key(header::ETAG) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("could not buffer response body: {:?}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&bytes);
+    let etag = format!("\"{:016x}\"", hasher.finish());
+    trace!("etag created: {}", etag);
+
+    match etag.parse::<ETag>() {
+        Ok(etag) => parts.headers.typed_insert(etag),
+        Err(e) => error!("could not create etag: {:?}", e),
+    }
+
+    Response::from_parts(parts, body::boxed(body::Full::from(bytes)))
+}
+
+/// Convert a response into a `304 Not Modified` response.
+fn not_modified(response: Response) -> Response {
+    let (mut parts, _) = response.into_parts();
+    parts.status = StatusCode::NOT_MODIFIED;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_TYPE);
+    Response::from_parts(parts, body::boxed(body::Empty::new()))
+}
+
+fn etag_label(etag: bool) -> &'static str {
+    if etag {
+        "etag"
+    } else {
+        "no-etag"
+    }
+}
+
+criterion_group!(benches, bench_cache_service);
+criterion_main!(benches);
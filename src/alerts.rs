@@ -0,0 +1,307 @@
+//! GitHub style alerts (callouts) within posts.
+//!
+//! An alert is a blockquote which starts with a marker on its own line:
+//!
+//! ```markdown
+//! > [!WARNING]
+//! > This will delete everything.
+//! ```
+//!
+//! The marker is replaced with a title and an icon, and the blockquote is
+//! given the `alert` class along with a class for the kind of alert (e.g.
+//! `alert-warning`) which are styled by the stylesheet.
+
+use markdown::mdast::{BlockQuote, Node};
+
+/// The kind of alert, this decides the title, icon and colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Information the reader should notice even when skimming.
+    Note,
+    /// Optional information to help the reader be more successful.
+    Tip,
+    /// Crucial information the reader needs to know.
+    Important,
+    /// Critical content that needs the reader's immediate attention.
+    Warning,
+    /// The negative consequences of an action.
+    Caution,
+}
+
+impl Kind {
+    const ALL: [Kind; 5] = [
+        Kind::Note,
+        Kind::Tip,
+        Kind::Important,
+        Kind::Warning,
+        Kind::Caution,
+    ];
+
+    /// The marker written at the start of the blockquote (e.g. `[!NOTE]`).
+    fn marker(self) -> &'static str {
+        match self {
+            Kind::Note => "[!NOTE]",
+            Kind::Tip => "[!TIP]",
+            Kind::Important => "[!IMPORTANT]",
+            Kind::Warning => "[!WARNING]",
+            Kind::Caution => "[!CAUTION]",
+        }
+    }
+
+    /// The title shown at the top of the alert in place of the marker.
+    fn title(self) -> &'static str {
+        match self {
+            Kind::Note => "Note",
+            Kind::Tip => "Tip",
+            Kind::Important => "Important",
+            Kind::Warning => "Warning",
+            Kind::Caution => "Caution",
+        }
+    }
+
+    /// The class of the blockquote used by the stylesheet to colour the
+    /// alert.
+    fn class(self) -> &'static str {
+        match self {
+            Kind::Note => "alert-note",
+            Kind::Tip => "alert-tip",
+            Kind::Important => "alert-important",
+            Kind::Warning => "alert-warning",
+            Kind::Caution => "alert-caution",
+        }
+    }
+
+    /// The shapes within the SVG icon, these are drawn using the text colour.
+    fn icon(self) -> &'static str {
+        match self {
+            // an "i" within a circle
+            Kind::Note => {
+                concat!(
+                    r#"<circle cx="12" cy="12" r="10"/>"#,
+                    r#"<path d="M12 16v-4M12 8h.01"/>"#,
+                )
+            }
+            // a light bulb
+            Kind::Tip => {
+                concat!(
+                    r#"<path d="M9 18h6M10 22h4M12 2a7 7 0 0 0-4 12.7V17h8"#,
+                    r#"v-2.3A7 7 0 0 0 12 2z"/>"#,
+                )
+            }
+            // an "!" within a speech bubble
+            Kind::Important => {
+                concat!(
+                    r#"<path d="M21 15a2 2 0 0 1-2 2H7l-4 4V5a2 2 0 0 1 2-2h14"#,
+                    r#"a2 2 0 0 1 2 2z"/><path d="M12 7v3M12 13h.01"/>"#,
+                )
+            }
+            // an "!" within a triangle
+            Kind::Warning => {
+                concat!(
+                    r#"<path d="M10.29 3.86 1.82 18a2 2 0 0 0 1.71 3h16.94"#,
+                    r#"a2 2 0 0 0 1.71-3L13.71 3.86a2 2 0 0 0-3.42 0z"/>"#,
+                    r#"<path d="M12 9v4M12 17h.01"/>"#,
+                )
+            }
+            // an "!" within an octagon
+            Kind::Caution => {
+                concat!(
+                    r#"<path d="M7.86 2h8.28L22 7.86v8.28L16.14 22H7.86"#,
+                    r#"L2 16.14V7.86z"/><path d="M12 8v4M12 16h.01"/>"#,
+                )
+            }
+        }
+    }
+
+    /// Find the kind of alert from the start of the blockquote's text.
+    ///
+    /// The marker is case-insensitive and must be on its own line.
+    fn from_text(text: &str) -> Option<Self> {
+        let line = text.lines().next()?.trim_end();
+        Kind::ALL
+            .into_iter()
+            .find(|kind| kind.marker().eq_ignore_ascii_case(line))
+    }
+}
+
+/// An alert found at the start of a blockquote.
+pub struct Alert<'a> {
+    pub kind: Kind,
+    /// The text after the marker's line within the first paragraph.
+    pub text: &'a str,
+    /// The nodes after the text within the first paragraph.
+    pub inlines: &'a [Node],
+    /// The nodes after the first paragraph.
+    pub blocks: &'a [Node],
+}
+
+/// Find the alert (if any) at the start of the blockquote.
+///
+/// The first line of the blockquote's first paragraph must be the marker
+/// and nothing else, otherwise it is an ordinary blockquote.
+pub fn find(blockquote: &BlockQuote) -> Option<Alert<'_>> {
+    let (Node::Paragraph(paragraph), blocks) =
+        blockquote.children.split_first()?
+    else {
+        return None;
+    };
+    let (Node::Text(text), mut inlines) = paragraph.children.split_first()?
+    else {
+        return None;
+    };
+
+    let kind = Kind::from_text(&text.value)?;
+    let text = text.value.split_once('\n').map_or("", |(_, text)| text);
+
+    // the marker can be ended with a hard break rather than a line ending
+    if text.is_empty() {
+        if let Some((Node::Break(_), rest)) = inlines.split_first() {
+            inlines = rest;
+        }
+    }
+
+    Some(Alert {
+        kind,
+        text,
+        inlines,
+        blocks,
+    })
+}
+
+/// The opening tag of the alert's blockquote followed by the title and icon
+/// of the alert.
+pub fn start(kind: Kind) -> String {
+    format!(
+        "<blockquote class=\"alert {class}\">\n<p class=\"alert-title\">\
+         <svg class=\"alert-icon\" viewBox=\"0 0 24 24\" width=\"16\" \
+         height=\"16\" fill=\"none\" stroke=\"currentColor\" \
+         stroke-width=\"2\" stroke-linecap=\"round\" \
+         stroke-linejoin=\"round\" aria-hidden=\"true\">{icon}</svg>\
+         {title}</p>",
+        class = kind.class(),
+        icon = kind.icon(),
+        title = kind.title(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Render the Markdown into HTML.
+    fn render(markdown: &str) -> String {
+        let ast = markdown::to_mdast(markdown, &crate::posts::parse_options())
+            .unwrap();
+        crate::html::render(&ast, &[], "")
+    }
+
+    /// Find the kind of alert of the first blockquote.
+    fn kind(markdown: &str) -> Option<Kind> {
+        let ast = markdown::to_mdast(markdown, &crate::posts::parse_options())
+            .unwrap();
+        let mut nodes = vec![&ast];
+        while let Some(node) = nodes.pop() {
+            if let Node::BlockQuote(blockquote) = node {
+                return find(blockquote).map(|alert| alert.kind);
+            }
+            nodes.extend(node.children().into_iter().flatten().rev());
+        }
+        panic!("no blockquote in {markdown:?}");
+    }
+
+    #[test]
+    fn renders_alerts() {
+        let html = render("> [!WARNING]\n> This will *delete* everything.");
+        assert_eq!(
+            html,
+            format!(
+                "{}\n<p>This will <em>delete</em> everything.</p>\n\
+                 </blockquote>",
+                start(Kind::Warning)
+            )
+        );
+        assert!(html.starts_with(
+            "<blockquote class=\"alert alert-warning\">\n\
+             <p class=\"alert-title\"><svg"
+        ));
+        assert!(html.contains("</svg>Warning</p>"));
+    }
+
+    #[test]
+    fn every_kind() {
+        for kind in Kind::ALL {
+            assert_eq!(
+                self::kind(&format!("> {}\n> Text", kind.marker())),
+                Some(kind)
+            );
+        }
+    }
+
+    #[test]
+    fn markers_are_case_insensitive() {
+        assert_eq!(kind("> [!note]\n> Text"), Some(Kind::Note));
+        assert_eq!(kind("> [!Tip]\n> Text"), Some(Kind::Tip));
+        assert_eq!(
+            render("> [!note]\n> Lowercase."),
+            format!("{}\n<p>Lowercase.</p>\n</blockquote>", start(Kind::Note))
+        );
+    }
+
+    #[test]
+    fn marker_must_be_on_its_own_line() {
+        assert_eq!(kind("> [!TIP] with trailing text\n> Text"), None);
+        assert_eq!(kind("> Text [!TIP]\n> Text"), None);
+        assert_eq!(kind("> [!UNKNOWN]\n> Text"), None);
+        assert_eq!(
+            render("> [!TIP] with trailing text"),
+            "<blockquote>\n<p>[!TIP] with trailing text</p>\n</blockquote>"
+        );
+
+        // trailing whitespace and a hard break are still on their own line
+        assert_eq!(kind("> [!TIP]  \n> Text"), Some(Kind::Tip));
+        assert_eq!(
+            render("> [!TIP]\\\n> Text"),
+            format!("{}\n<p>Text</p>\n</blockquote>", start(Kind::Tip))
+        );
+    }
+
+    #[test]
+    fn marker_must_start_the_first_paragraph() {
+        assert_eq!(kind("> - [!NOTE]\n> - Text"), None);
+        assert_eq!(kind("> ## [!NOTE]\n> Text"), None);
+        assert_eq!(kind("> **[!NOTE]**\n> Text"), None);
+        assert_eq!(kind(">\n> [!NOTE]\n> Text"), Some(Kind::Note));
+        assert_eq!(
+            render("> - [!NOTE]"),
+            "<blockquote>\n<ul>\n<li>[!NOTE]</li>\n</ul>\n</blockquote>"
+        );
+    }
+
+    #[test]
+    fn alerts_without_content() {
+        assert_eq!(
+            render("> [!CAUTION]"),
+            format!("{}\n</blockquote>", start(Kind::Caution))
+        );
+        assert_eq!(
+            render("> [!CAUTION]\n>\n> - Item"),
+            format!(
+                "{}\n<ul>\n<li>Item</li>\n</ul>\n</blockquote>",
+                start(Kind::Caution)
+            )
+        );
+    }
+
+    #[test]
+    fn alerts_within_other_blocks() {
+        assert_eq!(
+            render("- > [!IMPORTANT]\n  > In a list."),
+            format!(
+                "<ul>\n<li>\n{}\n<p>In a list.</p>\n</blockquote>\n</li>\n</ul>",
+                start(Kind::Important)
+            )
+        );
+        assert!(render("Text[^1]\n\n[^1]: > [!NOTE]\n    > In a footnote.")
+            .contains(&start(Kind::Note)));
+    }
+}
@@ -0,0 +1,262 @@
+//! Blog posts.
+//!
+//! Posts are loaded from the blog directory (default: `posts/blog`) and listed
+//! newest first across multiple pages, `/blog` is the first page and
+//! `/blog/page/:page` are the remaining pages.
+//!
+//! Draft posts (`draft: true` in the frontmatter or the file name starts with
+//! `_`) are only listed or served if the filter is disabled. Drafts are never
+//! included in the sitemap or static export.
+
+use std::cmp::Reverse;
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+use time::Date;
+
+use crate::config::Config;
+use crate::content::ContentStore;
+use crate::filters;
+use crate::posts::{Metadata, Post, Section};
+use crate::AppState;
+use crate::{search, tags};
+
+/// The number of posts listed on each page.
+pub(crate) const PAGE_SIZE: usize = 10;
+
+/// A page of the list of blog posts.
+#[derive(Template)]
+#[template(path = "pages/blog.html")]
+struct BlogPage<'a> {
+    config: &'a Config,
+    list: &'a [&'a Post<Frontmatter, Metadata>],
+    pagination: Pagination,
+}
+
+/// Individual blog post page.
+#[derive(Template)]
+#[template(path = "pages/blog_post.html")]
+struct BlogPostPage<'a> {
+    config: &'a Config,
+    post: &'a Post<Frontmatter, Metadata>,
+}
+
+/// Frontmatter from the `.md` files used to generate the posts.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub(crate) struct Frontmatter {
+    title: String,
+    summary: String,
+    /// The date the post was published.
+    #[serde(with = "crate::dates::iso")]
+    date: Date,
+    #[serde(default, with = "crate::dates::iso::option")]
+    updated: Option<Date>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    draft: bool,
+}
+
+impl Frontmatter {
+    /// The most recent of either the published or updated date.
+    pub(crate) fn most_recent(&self) -> Date {
+        crate::dates::most_recent(self.date, self.updated)
+    }
+}
+
+/// The current page and the total number of pages.
+#[derive(Debug, Clone, Copy)]
+struct Pagination {
+    /// The current page, starting at `1`.
+    page: usize,
+    pages: usize,
+}
+
+impl Pagination {
+    /// The path of the previous page (if there is one).
+    fn previous(&self) -> Option<String> {
+        (self.page > 1).then(|| page_path(self.page - 1))
+    }
+
+    /// The path of the next page (if there is one).
+    fn next(&self) -> Option<String> {
+        (self.page < self.pages).then(|| page_path(self.page + 1))
+    }
+}
+
+/// The path of a page of the list (e.g. `/blog/page/2`).
+///
+/// The first page is always `/blog`.
+pub(crate) fn page_path(page: usize) -> String {
+    match page {
+        1 => "/blog".to_owned(),
+        page => format!("/blog/page/{page}"),
+    }
+}
+
+/// The number of pages needed to list the posts.
+///
+/// There is always at least one page, even if there are no posts.
+pub(crate) fn page_count(posts: usize) -> usize {
+    posts.div_ceil(PAGE_SIZE).max(1)
+}
+
+/// Check if the post is a draft.
+fn is_draft(post: &Post<Frontmatter, Metadata>) -> bool {
+    post.frontmatter.draft || post.metadata.hidden
+}
+
+/// Every post that isn't a draft.
+pub(crate) fn published(
+    posts: &[Post<Frontmatter, Metadata>],
+) -> impl Iterator<Item = &Post<Frontmatter, Metadata>> {
+    posts.iter().filter(|p| !is_draft(p))
+}
+
+/// Every blog post within the blog directory (default: `posts/blog`) sorted
+/// by newest first.
+///
+/// Posts on the same day are sorted by title so the pages don't change
+/// between loads.
+pub(crate) fn section(
+    config: &Config,
+) -> Section<Frontmatter, (Date, Reverse<String>)> {
+    Section::new("blog", config.blog_dir(), "/blog", |f| {
+        (f.date, Reverse(f.title.clone()))
+    })
+}
+
+/// Create a search document for each blog post.
+pub(crate) fn search_documents(
+    posts: &[Post<Frontmatter, Metadata>],
+) -> impl Iterator<Item = search::Document> + '_ {
+    posts.iter().map(|p| search::Document {
+        section: "Blog",
+        title: p.frontmatter.title.clone(),
+        summary: p.frontmatter.summary.clone(),
+        path: p.metadata.slug.clone(),
+        text: p.text.clone(),
+        hidden: is_draft(p),
+    })
+}
+
+/// Create a tag entry for each blog post.
+pub(crate) fn tag_entries(
+    posts: &[Post<Frontmatter, Metadata>],
+) -> impl Iterator<Item = tags::Entry<'_>> {
+    posts.iter().map(|p| tags::Entry {
+        section: "Blog",
+        title: &p.frontmatter.title,
+        summary: Some(&p.frontmatter.summary),
+        path: &p.metadata.slug,
+        created_at: p.frontmatter.date,
+        updated_at: p.frontmatter.updated,
+        tags: &p.frontmatter.tags,
+        hidden: is_draft(p),
+    })
+}
+
+/// Render a page of the list of blog posts.
+///
+/// Responds with a 404 if the page doesn't exist.
+fn render_page(config: &Config, store: &ContentStore, page: usize) -> Response {
+    let content = store.get();
+    let list = content
+        .blog
+        .iter()
+        .filter(|p| {
+            // if the filter is disabled then we don't filter out any drafts
+            if config.disable_filter {
+                return true;
+            }
+
+            !is_draft(p)
+        })
+        .collect::<Vec<_>>();
+
+    let pagination = Pagination {
+        page,
+        pages: page_count(list.len()),
+    };
+    if page == 0 || page > pagination.pages {
+        info!("blog page not found: {}", page);
+        return crate::error::not_found_page(config);
+    }
+
+    let start = (page - 1) * PAGE_SIZE;
+    let list = &list[start..(start + PAGE_SIZE).min(list.len())];
+
+    debug!("{} blog posts listed on page {}", list.len(), page);
+
+    let page = BlogPage {
+        config,
+        list,
+        pagination,
+    };
+
+    page.into_response()
+}
+
+#[instrument(skip_all)]
+async fn get_blog(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+) -> Response {
+    render_page(&config, &store, 1)
+}
+
+#[instrument(skip(config, store))]
+async fn get_blog_page(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+    Path(page): Path<usize>,
+) -> Response {
+    // the first page is only served at `/blog`
+    if page == 1 {
+        return Redirect::permanent(&page_path(1)).into_response();
+    }
+
+    render_page(&config, &store, page)
+}
+
+#[instrument(skip(config, store))]
+async fn get_post_by_name(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+    Path(file): Path<String>,
+) -> Response {
+    let content = store.get();
+    let slug = format!("/blog/{file}");
+    let Some(post) = content
+        .blog
+        .iter()
+        .filter(|p| config.disable_filter || !is_draft(p))
+        .find(|p| p.metadata.slug == slug)
+    else {
+        // if the post doesn't exist (or is a draft) then return a 404
+        info!("blog post not found: {}", file);
+        return crate::error::not_found_page(&config);
+    };
+
+    trace!("blog post found: {}", slug);
+
+    let page = BlogPostPage {
+        config: &config,
+        post,
+    };
+
+    page.into_response()
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_blog))
+        .route("/page/:page", get(get_blog_page))
+        .route("/:file", get(get_post_by_name))
+}
@@ -1,21 +1,84 @@
-use std::task::{Context, Poll};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::SystemTime;
 
 use axum::{
-    body::Body,
-    http::{header, Request},
-    response::Response,
+    body::{self, Body, BoxBody, HttpBody},
+    headers::{ETag, HeaderMapExt, IfModifiedSince, IfNoneMatch, LastModified},
+    http::{
+        header, response::Parts, HeaderMap, HeaderName, HeaderValue, Method,
+        Request, StatusCode,
+    },
+    response::{IntoResponse, Response},
 };
-use futures_util::future::BoxFuture;
+use pin_project_lite::pin_project;
 use tower::{Layer, Service};
 
-#[derive(Debug, Clone, Default)]
+mod cache_control;
+mod response_cache;
+mod vary;
+
+pub use cache_control::CacheControl;
+pub use response_cache::{Limits, ResponseCache, ResponseCacheLayer};
+pub use vary::{merge_vary, VaryLayer};
+
+/// Set the `Cache-Control` header (and optionally an `ETag`) for every
+/// response.
+///
+/// The options can differ per route by adding a `policy` for a path pattern
+/// (e.g. long lived caching for static files). Error responses use separate
+/// options since they should never be cached (default: `no-store`).
+///
+/// If a response already has a `Cache-Control` header then the options are
+/// merged into it (see `CacheControl::merge`), so the layer can also be
+/// applied to a nested router to override the options of an outer layer.
+#[derive(Debug, Clone)]
 pub struct CacheLayer {
+    /// The options used when no policy matches the request path.
     pub options: Options,
+    /// The options used for client and server error responses.
+    pub errors: Options,
+    policies: Arc<Vec<Policy>>,
+}
+
+impl Default for CacheLayer {
+    fn default() -> Self {
+        Self::new(Options::default())
+    }
 }
 
 impl CacheLayer {
     pub fn new(options: Options) -> Self {
-        Self { options }
+        Self {
+            options,
+            errors: Options {
+                cache_control: CacheControl::new().public().no_store(),
+                etag: false,
+            },
+            policies: Arc::default(),
+        }
+    }
+
+    /// Use different options for requests with a path matching the pattern.
+    ///
+    /// The pattern is either an exact path (e.g. `/favicon.ico`) or a prefix
+    /// ending with `/*` (e.g. `/public/*`). The policies are checked in the
+    /// order they are added and the first match is used.
+    pub fn policy(mut self, pattern: &str, options: Options) -> Self {
+        Arc::make_mut(&mut self.policies).push(Policy {
+            pattern: pattern.to_owned(),
+            options,
+        });
+        self
+    }
+
+    /// Use different options for client and server error responses.
+    pub fn errors(mut self, options: Options) -> Self {
+        self.errors = options;
+        self
     }
 }
 
@@ -25,62 +88,99 @@ impl<S> Layer<S> for CacheLayer {
     fn layer(&self, inner: S) -> Self::Service {
         CacheService {
             inner,
-            options: self.options,
+            options: self.options.clone(),
+            errors: self.errors.clone(),
+            policies: self.policies.clone(),
+        }
+    }
+}
+
+/// The options used for requests with a path matching the pattern.
+#[derive(Debug, Clone)]
+struct Policy {
+    pattern: String,
+    options: Options,
+}
+
+impl Policy {
+    fn matches(&self, path: &str) -> bool {
+        matches_pattern(&self.pattern, path)
+    }
+}
+
+/// Check if the path matches the pattern.
+///
+/// The pattern is either an exact path (e.g. `/favicon.ico`) or a prefix
+/// ending with `/*` (e.g. `/public/*`).
+fn matches_pattern(pattern: &str, path: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(prefix) => {
+            path == prefix
+                || path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/'))
         }
+        None => path == pattern,
     }
 }
 
+/// The content type of a stream of server-sent events.
+pub const EVENT_STREAM: &str = "text/event-stream";
+
+/// Check if the response is a stream of server-sent events (which never
+/// ends, so the body must not be buffered).
+pub(crate) fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|ct| ct.as_bytes().starts_with(EVENT_STREAM.as_bytes()))
+}
+
 /// Options for the caching layer.
 ///
 /// The default is:
 /// - `Cache-Control: public, no-cache, max-age=0`
 ///
-/// **Note:** the default doesn't use either `Last-Modified` or `ETag` headers.
-#[derive(Debug, Clone, Copy, Default)]
+/// **Note:** the default doesn't create `ETag` headers. However, if a response
+/// already has an `ETag` or `Last-Modified` header then conditional requests
+/// (`If-None-Match` and `If-Modified-Since`) are always answered with
+/// `304 Not Modified` when the resource hasn't changed.
+#[derive(Debug, Clone)]
 pub struct Options {
-    /// The core use-cases of the `Cache-Control` header.
+    /// The directives of the `Cache-Control` header.
     pub cache_control: CacheControl,
-    /// Should the `Cache-Control` use `private` or `public` (default: false).
-    pub private: bool,
-    /// Should the `Cache-Control` use `immutable` (default: false).
-    pub immutable: bool,
+    /// Should a strong `ETag` be created from the response body
+    /// (default: false).
+    ///
+    /// This requires the entire body to be buffered so it is only done for
+    /// successful responses to `GET` and `HEAD` requests that are not
+    /// server-sent events.
+    pub etag: bool,
 }
 
-/// The core use-cases of the `Cache-Control` header.
-///
-/// Note: This doesn't include *all* of the possible options.
-#[derive(Debug, Clone, Copy, Default)]
-pub enum CacheControl {
-    /// Cache-Control: `no-cache, max-age=0`
-    #[default]
-    NoCache,
-    /// Cache-Control: `no-store`
-    NoStore,
-    /// Cache-Control: `max-age=<seconds>`
-    MaxAge(u32),
-    /// Cache-Control: `max-age=<seconds>, must-revalidate`
-    MustRevalidate(u32),
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            cache_control: CacheControl::new().public().no_cache().max_age(0),
+            etag: false,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct CacheService<S> {
     inner: S,
     options: Options,
+    errors: Options,
+    policies: Arc<Vec<Policy>>,
 }
 
 impl<S> Service<Request<Body>> for CacheService<S>
 where
-    S: Service<Request<Body>, Response = Response> + Send + 'static,
-    S::Future: Send + 'static,
+    S: Service<Request<Body>, Response = Response>,
 {
     type Response = S::Response;
-
-    // TODO: use a custom error type to avoid using `.unwrap()`
     type Error = S::Error;
-
-    // TODO: implement my own future that is not boxed (hard to do)
-    // Source: https://github.com/tower-rs/tower/blob/master/guides/building-a-middleware-from-scratch.md
-    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+    type Future = ResponseFuture<S::Future>;
 
     fn poll_ready(
         &mut self,
@@ -90,49 +190,444 @@ where
     }
 
     #[instrument(skip_all)]
-    fn call(&mut self, request: Request<Body>) -> Self::Future {
-        let future = self.inner.call(request);
-
-        let cache_control = {
-            let publicity = if self.options.private {
-                "private, "
-            } else {
-                "public, "
-            };
-
-            let core = match self.options.cache_control {
-                CacheControl::NoCache => "no-cache, max-age=0".to_string(),
-                CacheControl::NoStore => "no-store".to_string(),
-                CacheControl::MaxAge(secs) => format!("max-age={}", secs),
-                CacheControl::MustRevalidate(secs) => {
-                    format!("max-age={}, must-revalidate", secs)
-                }
-            };
+    fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+        let conditions = Conditions::from_request(&request);
+        let path = request.uri().path();
+        let options = self
+            .policies
+            .iter()
+            .find(|p| p.matches(path))
+            .map_or(&self.options, |p| &p.options)
+            .clone();
 
-            let immutable = if self.options.immutable {
-                ", immutable"
-            } else {
-                ""
-            };
+        // the router drops the body of the response to a `HEAD` request so
+        // the `ETag` would be created from an empty body, instead the request
+        // is sent as a `GET` and the body is dropped once the `ETag` is set
+        let head = options.etag && request.method() == Method::HEAD;
+        if head {
+            *request.method_mut() = Method::GET;
+        }
 
-            format!("{}{}{}", publicity, core, immutable)
-        };
-        trace!("cache-control header created: {}", cache_control);
+        ResponseFuture {
+            state: State::Inner {
+                future: self.inner.call(request),
+            },
+            conditions,
+            options,
+            errors: self.errors.clone(),
+            head,
+        }
+    }
+}
+
+pin_project! {
+    /// The response future of `CacheService`.
+    ///
+    /// This is a state machine rather than a boxed `async` block so that a
+    /// request doesn't need an extra allocation.
+    /// Source: https://github.com/tower-rs/tower/blob/master/guides/building-a-middleware-from-scratch.md
+    pub struct ResponseFuture<F> {
+        #[pin]
+        state: State<F>,
+        conditions: Conditions,
+        options: Options,
+        errors: Options,
+        // the request was a `HEAD` request sent as a `GET` request
+        head: bool,
+    }
+}
+
+pin_project! {
+    #[project = StateProj]
+    enum State<F> {
+        /// Waiting for the inner service to respond.
+        Inner {
+            #[pin]
+            future: F,
+        },
+        /// Buffering the response body to create an `ETag`.
+        Buffering {
+            parts: Option<Parts>,
+            body: BoxBody,
+            bytes: Vec<u8>,
+        },
+        /// The response has been returned.
+        Done,
+    }
+}
 
-        Box::pin(async move {
-            let mut response: Response = future.await?;
+impl<F, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response, E>>,
+{
+    type Output = Result<Response, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        loop {
+            match this.state.as_mut().project() {
+                StateProj::Inner { future } => {
+                    let mut response = ready!(future.poll(cx))?;
+
+                    let status = response.status();
+                    let options = if status.is_client_error()
+                        || status.is_server_error()
+                    {
+                        &*this.errors
+                    } else {
+                        &*this.options
+                    };
+
+                    if let Err(e) =
+                        set_cache_control(&mut response, &options.cache_control)
+                    {
+                        this.state.set(State::Done);
+                        return Poll::Ready(Ok(e.into_response()));
+                    }
+
+                    if options.etag
+                        && this.conditions.cacheable(&response)
+                        && needs_etag(&response)
+                    {
+                        let (parts, body) = response.into_parts();
+                        this.state.set(State::Buffering {
+                            parts: Some(parts),
+                            body,
+                            bytes: Vec::new(),
+                        });
+                        continue;
+                    }
+
+                    this.state.set(State::Done);
+                    let response = this.conditions.respond(response);
+                    return Poll::Ready(Ok(strip_body(response, *this.head)));
+                }
+                StateProj::Buffering { parts, body, bytes } => {
+                    while let Some(chunk) =
+                        ready!(Pin::new(&mut *body).poll_data(cx))
+                    {
+                        match chunk {
+                            Ok(chunk) => bytes.extend_from_slice(&chunk),
+                            Err(e) => {
+                                error!("could not buffer response body: {e:?}");
+                                this.state.set(State::Done);
+                                return Poll::Ready(Ok(
+                                    StatusCode::INTERNAL_SERVER_ERROR
+                                        .into_response(),
+                                ));
+                            }
+                        }
+                    }
 
-            if let Some(cc) = response.headers().get(header::CACHE_CONTROL) {
-                debug!("response already contains the cache-control header: {cc:?}");
-                return Ok(response);
+                    let parts =
+                        parts.take().expect("parts are only taken once");
+                    let response = with_etag(parts, std::mem::take(bytes));
+
+                    this.state.set(State::Done);
+                    let response = match response {
+                        Ok(response) => response,
+                        Err(e) => return Poll::Ready(Ok(e.into_response())),
+                    };
+                    let response = this.conditions.respond(response);
+                    return Poll::Ready(Ok(strip_body(response, *this.head)));
+                }
+                StateProj::Done => {
+                    panic!("ResponseFuture polled after completion")
+                }
             }
+        }
+    }
+}
+
+/// Merge the options into the `Cache-Control` header of the response so that
+/// the directives set by the handler take precedence.
+///
+/// An existing header that can't be parsed is kept as is.
+fn set_cache_control(
+    response: &mut Response,
+    options: &CacheControl,
+) -> Result<(), HeaderError> {
+    let cache_control = match response.headers().typed_try_get::<CacheControl>()
+    {
+        Ok(existing) => existing.unwrap_or_default().merge(options),
+        Err(e) => {
+            let cc = response.headers().get(header::CACHE_CONTROL);
+            warn!("invalid cache-control header {cc:?}: {e}");
+            return Ok(());
+        }
+    };
+
+    let value = cache_control.to_header_value()?;
+    trace!("cache-control header created: {:?}", value);
+
+    response.headers_mut().insert(header::CACHE_CONTROL, value);
+    Ok(())
+}
+
+/// The conditional request headers used to decide if a `304 Not Modified`
+/// response can be sent.
+#[derive(Debug, Clone)]
+struct Conditions {
+    /// Only `GET` and `HEAD` requests are conditional.
+    safe_method: bool,
+    if_none_match: Option<IfNoneMatch>,
+    if_modified_since: Option<IfModifiedSince>,
+}
+
+impl Conditions {
+    fn from_request(request: &Request<Body>) -> Self {
+        let headers = request.headers();
+        Self {
+            safe_method: matches!(
+                *request.method(),
+                Method::GET | Method::HEAD
+            ),
+            if_none_match: headers.typed_get(),
+            if_modified_since: headers.typed_get(),
+        }
+    }
+
+    /// Check if the response can be used for validation (i.e. it is a
+    /// successful response to a `GET` or `HEAD` request).
+    fn cacheable(&self, response: &Response) -> bool {
+        self.safe_method && response.status() == StatusCode::OK
+    }
+
+    /// Check if the client already has the current version of the response.
+    ///
+    /// `If-None-Match` takes precedence over `If-Modified-Since`.
+    /// Source: https://www.rfc-editor.org/rfc/rfc9110#section-13.2.2
+    fn not_modified(&self, response: &Response) -> bool {
+        if !self.cacheable(response) {
+            return false;
+        }
+
+        let headers = response.headers();
+
+        if let Some(if_none_match) = &self.if_none_match {
+            return headers
+                .typed_get::<ETag>()
+                .is_some_and(|etag| !if_none_match.precondition_passes(&etag));
+        }
+
+        if let Some(if_modified_since) = &self.if_modified_since {
+            return headers.typed_get::<LastModified>().is_some_and(|lm| {
+                !if_modified_since.is_modified(SystemTime::from(lm))
+            });
+        }
+
+        false
+    }
+
+    /// Answer with `304 Not Modified` if the client already has the current
+    /// version of the response.
+    fn respond(&self, response: Response) -> Response {
+        if self.not_modified(&response) {
+            debug!("resource not modified");
+            return not_modified(response);
+        }
+
+        response
+    }
+}
+
+/// Check if a strong `ETag` should be created from the response body.
+///
+/// An `ETag` isn't created if the response already has one or if it is a
+/// stream of server-sent events (which never ends).
+fn needs_etag(response: &Response) -> bool {
+    let headers = response.headers();
+    !is_event_stream(headers) && !headers.contains_key(header::ETAG)
+}
+
+/// Rebuild a response from the buffered body and set a strong `ETag` from the
+/// hash of the body.
+fn with_etag(
+    mut parts: Parts,
+    bytes: Vec<u8>,
+) -> Result<Response, HeaderError> {
+    let etag = etag(&bytes)?;
+    trace!("etag created: {:?}", etag);
+    parts.headers.typed_insert(etag);
+
+    Ok(Response::from_parts(
+        parts,
+        body::boxed(body::Full::from(bytes)),
+    ))
+}
+
+/// Create a strong `ETag` from the hash of the body.
+fn etag(bytes: &[u8]) -> Result<ETag, HeaderError> {
+    let etag = format!("\"{:016x}\"", fnv1a(bytes));
+
+    etag.parse()
+        .map_err(|_| HeaderError::new(header::ETAG.clone(), etag))
+}
+
+/// The 64-bit FNV-1a hash of the bytes.
+///
+/// The hash doesn't need to be cryptographic, it only needs to change when the
+/// body changes. However, it must be the same between builds (unlike
+/// `DefaultHasher`) so that a deploy doesn't change every `ETag`.
+/// Source: http://www.isthe.com/chongo/tech/comp/fnv/index.html
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
 
-            response.headers_mut().insert(
-                header::CACHE_CONTROL,
-                header::HeaderValue::try_from(&cache_control).unwrap(),
-            );
+/// Drop the body of the response to a `HEAD` request that was sent as a `GET`
+/// request.
+///
+/// The `Content-Length` is kept (if known) since it is the length of the body
+/// that would have been sent.
+fn strip_body(response: Response, head: bool) -> Response {
+    if !head {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    if let Some(length) = body.size_hint().exact() {
+        parts
+            .headers
+            .entry(header::CONTENT_LENGTH)
+            .or_insert_with(|| HeaderValue::from(length));
+    }
+
+    Response::from_parts(parts, body::boxed(body::Empty::new()))
+}
 
-            Ok(response)
-        })
+/// Convert a response into a `304 Not Modified` response.
+///
+/// The headers are kept (e.g. `ETag`, `Cache-Control` and `Vary`) except for
+/// the headers that describe the body.
+fn not_modified(response: Response) -> Response {
+    let (mut parts, _) = response.into_parts();
+    parts.status = StatusCode::NOT_MODIFIED;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_TYPE);
+    Response::from_parts(parts, body::boxed(body::Empty::new()))
+}
+
+/// An error when a header value can't be created.
+#[derive(Debug)]
+pub struct HeaderError {
+    name: HeaderName,
+    value: String,
+}
+
+impl HeaderError {
+    pub fn new(name: HeaderName, value: impl Into<String>) -> Self {
+        Self {
+            name,
+            value: value.into(),
+        }
+    }
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {} header value: {:?}", self.name, self.value)
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+/// Respond with a `500 Internal Server Error` since the response can't be sent
+/// without the header (e.g. it could make an error cacheable).
+impl IntoResponse for HeaderError {
+    fn into_response(self) -> Response {
+        error!("could not create response: {self}");
+
+        let mut response = (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("could not create the {} header", self.name),
+        )
+            .into_response();
+        response.headers_mut().insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("no-store"),
+        );
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    const PAGE: &str = "<h1>Hello, world!</h1>";
+
+    fn router() -> Router {
+        Router::new()
+            .route("/", get(|| async { PAGE }))
+            .layer(CacheLayer::new(Options {
+                etag: true,
+                ..Default::default()
+            }))
+    }
+
+    async fn send(method: Method) -> Response {
+        let request = Request::builder()
+            .method(method)
+            .uri("/")
+            .body(Body::empty())
+            .unwrap();
+        router().oneshot(request).await.unwrap()
+    }
+
+    #[test]
+    fn header_error_is_a_server_error() {
+        let error = HeaderError::new(header::ETAG, "\n");
+        let response = error.into_response();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+    }
+
+    #[test]
+    fn fnv1a_is_stable() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[tokio::test]
+    async fn head_has_same_etag_as_get() {
+        let get = send(Method::GET).await;
+        let head = send(Method::HEAD).await;
+
+        let etag = get.headers().get(header::ETAG).unwrap();
+        assert_eq!(head.headers().get(header::ETAG), Some(etag));
+        assert_eq!(
+            head.headers().get(header::CONTENT_LENGTH).unwrap(),
+            &PAGE.len().to_string()
+        );
+
+        let body = hyper::body::to_bytes(head.into_body()).await.unwrap();
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn not_modified_when_etag_matches() {
+        let etag = send(Method::GET).await.headers()[header::ETAG].clone();
+
+        for method in [Method::GET, Method::HEAD] {
+            let request = Request::builder()
+                .method(method)
+                .uri("/")
+                .header(header::IF_NONE_MATCH, etag.clone())
+                .body(Body::empty())
+                .unwrap();
+            let response = router().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        }
     }
 }
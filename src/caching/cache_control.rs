@@ -0,0 +1,726 @@
+//! A typed `Cache-Control` header.
+//!
+//! Every directive from RFC 9111 (and the `immutable`, `stale-while-revalidate`
+//! and `stale-if-error` extensions) is modelled so that a header value can be
+//! both created and parsed. Unknown directives are kept so that a parsed
+//! header renders to the same directives.
+//!
+//! Source: https://www.rfc-editor.org/rfc/rfc9111#section-5.2
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use axum::{
+    headers::{self, Header},
+    http::{header, HeaderName, HeaderValue},
+};
+
+use super::HeaderError;
+
+/// The largest value of a `delta-seconds` directive, larger values are
+/// treated as this value.
+///
+/// Source: https://www.rfc-editor.org/rfc/rfc9111#section-1.2.2
+const MAX_DELTA_SECONDS: u32 = 2_147_483_648;
+
+/// The directives of a `Cache-Control` header.
+///
+/// A header can be created with the builder methods:
+///
+/// ```
+/// let cc = CacheControl::new().public().max_age(60).must_revalidate();
+/// assert_eq!(cc.to_string(), "public, max-age=60, must-revalidate");
+/// ```
+///
+/// **Note:** the optional field names of the `private` and `no-cache`
+/// directives are not kept, so a parsed `private="set-cookie"` is treated as
+/// `private` (which is more restrictive).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    /// `public`: any cache may store the response.
+    pub public: bool,
+    /// `private`: only a private cache (e.g. the browser) may store the
+    /// response.
+    pub private: bool,
+    /// `no-cache`: the response must be validated before it is used.
+    pub no_cache: bool,
+    /// `no-store`: the response must not be stored.
+    pub no_store: bool,
+    /// `no-transform`: intermediaries must not transform the content.
+    pub no_transform: bool,
+    /// `must-revalidate`: a stale response must be validated before it is
+    /// used.
+    pub must_revalidate: bool,
+    /// `proxy-revalidate`: same as `must-revalidate` but only for shared
+    /// caches.
+    pub proxy_revalidate: bool,
+    /// `must-understand`: only store the response if the status code is
+    /// understood.
+    pub must_understand: bool,
+    /// `immutable`: the response will not change while it is fresh.
+    pub immutable: bool,
+    /// `max-age=<seconds>`: how long the response is fresh for.
+    pub max_age: Option<u32>,
+    /// `s-maxage=<seconds>`: overrides `max-age` for shared caches.
+    pub s_maxage: Option<u32>,
+    /// `stale-while-revalidate=<seconds>`: how long a stale response can be
+    /// used while it is validated in the background.
+    pub stale_while_revalidate: Option<u32>,
+    /// `stale-if-error=<seconds>`: how long a stale response can be used if
+    /// validating it fails.
+    pub stale_if_error: Option<u32>,
+    /// `max-stale[=<seconds>]` (request only): accept a stale response, with
+    /// an optional limit on how stale.
+    pub max_stale: Option<Option<u32>>,
+    /// `min-fresh=<seconds>` (request only): the response must stay fresh for
+    /// at least this long.
+    pub min_fresh: Option<u32>,
+    /// `only-if-cached` (request only): only use a stored response.
+    pub only_if_cached: bool,
+    /// Unknown directives with their (unquoted) value.
+    extensions: Vec<(String, Option<String>)>,
+}
+
+impl CacheControl {
+    /// Create an empty header (i.e. no directives).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn public(mut self) -> Self {
+        self.public = true;
+        self
+    }
+
+    pub fn private(mut self) -> Self {
+        self.private = true;
+        self
+    }
+
+    pub fn no_cache(mut self) -> Self {
+        self.no_cache = true;
+        self
+    }
+
+    pub fn no_store(mut self) -> Self {
+        self.no_store = true;
+        self
+    }
+
+    pub fn no_transform(mut self) -> Self {
+        self.no_transform = true;
+        self
+    }
+
+    pub fn must_revalidate(mut self) -> Self {
+        self.must_revalidate = true;
+        self
+    }
+
+    pub fn proxy_revalidate(mut self) -> Self {
+        self.proxy_revalidate = true;
+        self
+    }
+
+    pub fn must_understand(mut self) -> Self {
+        self.must_understand = true;
+        self
+    }
+
+    pub fn immutable(mut self) -> Self {
+        self.immutable = true;
+        self
+    }
+
+    pub fn max_age(mut self, secs: u32) -> Self {
+        self.max_age = Some(secs);
+        self
+    }
+
+    pub fn s_maxage(mut self, secs: u32) -> Self {
+        self.s_maxage = Some(secs);
+        self
+    }
+
+    pub fn stale_while_revalidate(mut self, secs: u32) -> Self {
+        self.stale_while_revalidate = Some(secs);
+        self
+    }
+
+    pub fn stale_if_error(mut self, secs: u32) -> Self {
+        self.stale_if_error = Some(secs);
+        self
+    }
+
+    pub fn max_stale(mut self, secs: Option<u32>) -> Self {
+        self.max_stale = Some(secs);
+        self
+    }
+
+    pub fn min_fresh(mut self, secs: u32) -> Self {
+        self.min_fresh = Some(secs);
+        self
+    }
+
+    pub fn only_if_cached(mut self) -> Self {
+        self.only_if_cached = true;
+        self
+    }
+
+    /// Unknown directives with their (unquoted) value.
+    pub fn extensions(&self) -> &[(String, Option<String>)] {
+        &self.extensions
+    }
+
+    /// Create the header value.
+    pub fn to_header_value(&self) -> Result<HeaderValue, HeaderError> {
+        let value = self.to_string();
+        HeaderValue::from_str(&value)
+            .map_err(|_| HeaderError::new(header::CACHE_CONTROL, value))
+    }
+
+    /// Check if there are no directives.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Merge the directives of `other` into these directives, where these
+    /// directives take precedence.
+    ///
+    /// Directives that would conflict are merged as a group, the group is
+    /// only taken from `other` if none of the directives in the group are
+    /// set:
+    /// - visibility: `public` and `private`
+    /// - freshness: every directive that controls how long the response is
+    ///   fresh for or how it is validated (e.g. `no-cache` and `max-age`)
+    ///
+    /// The remaining directives are combined.
+    pub fn merge(mut self, other: &Self) -> Self {
+        if !self.has_visibility() {
+            self.public = other.public;
+            self.private = other.private;
+        }
+
+        if !self.has_freshness() {
+            self.no_cache = other.no_cache;
+            self.no_store = other.no_store;
+            self.must_revalidate = other.must_revalidate;
+            self.proxy_revalidate = other.proxy_revalidate;
+            self.immutable = other.immutable;
+            self.max_age = other.max_age;
+            self.s_maxage = other.s_maxage;
+            self.stale_while_revalidate = other.stale_while_revalidate;
+            self.stale_if_error = other.stale_if_error;
+        }
+
+        self.no_transform |= other.no_transform;
+        self.must_understand |= other.must_understand;
+        self.only_if_cached |= other.only_if_cached;
+        self.max_stale = self.max_stale.or(other.max_stale);
+        self.min_fresh = self.min_fresh.or(other.min_fresh);
+
+        for (name, value) in &other.extensions {
+            if !self.extensions.iter().any(|(n, _)| n == name) {
+                self.extensions.push((name.clone(), value.clone()));
+            }
+        }
+
+        self
+    }
+
+    fn has_visibility(&self) -> bool {
+        self.public || self.private
+    }
+
+    fn has_freshness(&self) -> bool {
+        self.no_cache
+            || self.no_store
+            || self.must_revalidate
+            || self.proxy_revalidate
+            || self.immutable
+            || self.max_age.is_some()
+            || self.s_maxage.is_some()
+            || self.stale_while_revalidate.is_some()
+            || self.stale_if_error.is_some()
+    }
+}
+
+impl fmt::Display for CacheControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = [
+            (self.public, "public"),
+            (self.private, "private"),
+            (self.no_cache, "no-cache"),
+            (self.no_store, "no-store"),
+        ];
+        let secs = [(self.max_age, "max-age"), (self.s_maxage, "s-maxage")];
+        let more_flags = [
+            (self.must_revalidate, "must-revalidate"),
+            (self.proxy_revalidate, "proxy-revalidate"),
+            (self.must_understand, "must-understand"),
+            (self.no_transform, "no-transform"),
+            (self.immutable, "immutable"),
+        ];
+        let more_secs = [
+            (self.stale_while_revalidate, "stale-while-revalidate"),
+            (self.stale_if_error, "stale-if-error"),
+        ];
+
+        let mut directives = Vec::new();
+        directives.extend(flags.iter().filter(|f| f.0).map(|f| f.1.to_owned()));
+        directives.extend(
+            secs.iter()
+                .filter_map(|(s, name)| s.map(|s| format!("{name}={s}"))),
+        );
+        directives
+            .extend(more_flags.iter().filter(|f| f.0).map(|f| f.1.to_owned()));
+        directives.extend(
+            more_secs
+                .iter()
+                .filter_map(|(s, name)| s.map(|s| format!("{name}={s}"))),
+        );
+
+        match self.max_stale {
+            Some(Some(secs)) => directives.push(format!("max-stale={secs}")),
+            Some(None) => directives.push("max-stale".to_owned()),
+            None => {}
+        }
+        if let Some(secs) = self.min_fresh {
+            directives.push(format!("min-fresh={secs}"));
+        }
+        if self.only_if_cached {
+            directives.push("only-if-cached".to_owned());
+        }
+
+        for (name, value) in &self.extensions {
+            match value {
+                Some(v) if is_token(v) => {
+                    directives.push(format!("{name}={v}"))
+                }
+                Some(v) => directives.push(format!("{name}={}", quote(v))),
+                None => directives.push(name.clone()),
+            }
+        }
+
+        f.write_str(&directives.join(", "))
+    }
+}
+
+impl FromStr for CacheControl {
+    type Err = anyhow::Error;
+
+    /// Parse a header value.
+    ///
+    /// Directive names are case-insensitive and empty directives are
+    /// ignored. Values may be quoted even when a token is expected.
+    ///
+    /// If a directive is repeated then the first value is used.
+    /// Source: https://www.rfc-editor.org/rfc/rfc9111#section-4.2.1
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cc = Self::default();
+
+        for directive in split_directives(s)? {
+            let directive = directive.trim();
+            if directive.is_empty() {
+                continue;
+            }
+
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (directive, None),
+            };
+
+            if !is_token(name) {
+                bail!("invalid cache-control directive: {directive:?}");
+            }
+            let value = value.map(unquote).transpose()?;
+            let name = name.to_ascii_lowercase();
+
+            let secs = || -> anyhow::Result<u32> {
+                let Some(value) = &value else {
+                    bail!("missing value for the {name} directive");
+                };
+                delta_seconds(value)
+                    .with_context(|| format!("invalid value for {name}"))
+            };
+
+            match name.as_str() {
+                "public" => cc.public = true,
+                "private" => cc.private = true,
+                "no-cache" => cc.no_cache = true,
+                "no-store" => cc.no_store = true,
+                "no-transform" => cc.no_transform = true,
+                "must-revalidate" => cc.must_revalidate = true,
+                "proxy-revalidate" => cc.proxy_revalidate = true,
+                "must-understand" => cc.must_understand = true,
+                "immutable" => cc.immutable = true,
+                "only-if-cached" => cc.only_if_cached = true,
+                "max-age" => first(&mut cc.max_age, secs()?),
+                "s-maxage" => first(&mut cc.s_maxage, secs()?),
+                "stale-while-revalidate" => {
+                    first(&mut cc.stale_while_revalidate, secs()?)
+                }
+                "stale-if-error" => first(&mut cc.stale_if_error, secs()?),
+                "min-fresh" => first(&mut cc.min_fresh, secs()?),
+                "max-stale" => first(
+                    &mut cc.max_stale,
+                    value.is_some().then(secs).transpose()?,
+                ),
+                _ if cc.extensions.iter().any(|(n, _)| *n == name) => {}
+                _ => cc.extensions.push((name, value)),
+            }
+        }
+
+        Ok(cc)
+    }
+}
+
+impl Header for CacheControl {
+    fn name() -> &'static HeaderName {
+        &header::CACHE_CONTROL
+    }
+
+    /// Decode every `Cache-Control` header into a single set of directives.
+    fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let mut values = values.peekable();
+        if values.peek().is_none() {
+            return Err(headers::Error::invalid());
+        }
+
+        let mut joined = String::new();
+        for value in values {
+            let value =
+                value.to_str().map_err(|_| headers::Error::invalid())?;
+            if !joined.is_empty() {
+                joined.push_str(", ");
+            }
+            joined.push_str(value);
+        }
+
+        joined.parse().map_err(|_| headers::Error::invalid())
+    }
+
+    fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+        // the directive names are tokens and the values are either tokens or
+        // quoted strings (checked when parsing) so this should never fail
+        match self.to_header_value() {
+            Ok(value) => values.extend(std::iter::once(value)),
+            Err(e) => error!("{e}"),
+        }
+    }
+}
+
+/// Set the value of a directive unless it has already been set.
+fn first<T>(directive: &mut Option<T>, value: T) {
+    directive.get_or_insert(value);
+}
+
+/// Split a header value on the commas that are not within a quoted string.
+fn split_directives(s: &str) -> anyhow::Result<Vec<&str>> {
+    let mut directives = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                directives.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if quoted {
+        bail!("unterminated quoted string in cache-control: {s:?}");
+    }
+    directives.push(&s[start..]);
+
+    Ok(directives)
+}
+
+/// Parse a `delta-seconds` value, saturating at `MAX_DELTA_SECONDS`.
+fn delta_seconds(value: &str) -> anyhow::Result<u32> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        bail!("{value:?} is not a number of seconds");
+    }
+
+    // the value is only digits so the only possible error is overflow
+    let secs = value.parse::<u64>().unwrap_or(u64::MAX);
+    Ok(secs.min(MAX_DELTA_SECONDS as u64) as u32)
+}
+
+/// Check if the string is a `token`.
+///
+/// Source: https://www.rfc-editor.org/rfc/rfc9110#section-5.6.2
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes().all(|b| {
+            b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+        })
+}
+
+/// Get the value of a `token` or `quoted-string`.
+fn unquote(value: &str) -> anyhow::Result<String> {
+    let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+    else {
+        if !is_token(value) {
+            bail!("invalid cache-control value: {value:?}");
+        }
+        return Ok(value.to_owned());
+    };
+
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            chars.next().unwrap_or(c)
+        } else {
+            c
+        };
+        // only visible ASCII, spaces and tabs are allowed
+        if c != '\t' && !(' '..='~').contains(&c) {
+            bail!("invalid character in cache-control value: {value:?}");
+        }
+        unquoted.push(c);
+    }
+
+    Ok(unquoted)
+}
+
+/// Create a `quoted-string` from a value.
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> CacheControl {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn builder() {
+        // the example from the documentation of `CacheControl`
+        let cc = CacheControl::new().public().max_age(60).must_revalidate();
+        assert_eq!(cc.to_string(), "public, max-age=60, must-revalidate");
+    }
+
+    #[test]
+    fn parse_directives() {
+        let cc = parse("Public, MAX-AGE=60 ,, no-transform, max-stale");
+        assert_eq!(
+            cc,
+            CacheControl::new()
+                .public()
+                .max_age(60)
+                .no_transform()
+                .max_stale(None)
+        );
+        assert_eq!(parse("max-stale=5").max_stale, Some(Some(5)));
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    fn parse_quoted_values() {
+        assert_eq!(parse("max-age=\"60\"").max_age, Some(60));
+
+        let cc = parse(r#"ext="a, b", other="say \"hi\" \\o/""#);
+        assert_eq!(
+            cc.extensions(),
+            [
+                ("ext".to_owned(), Some("a, b".to_owned())),
+                ("other".to_owned(), Some(r#"say "hi" \o/"#.to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for s in [
+            "max-age",
+            "max-age=",
+            "max-age=-1",
+            "max-age=1.5",
+            "max-age=ten",
+            "ext=\"unterminated",
+            "ext=a b",
+            "bad name",
+            "=60",
+            "ext=\"\u{e9}\"",
+        ] {
+            assert!(s.parse::<CacheControl>().is_err(), "{s:?} was parsed");
+        }
+    }
+
+    #[test]
+    fn delta_seconds_saturate() {
+        assert_eq!(delta_seconds("0").unwrap(), 0);
+        assert_eq!(delta_seconds("2147483647").unwrap(), 2_147_483_647);
+        assert_eq!(delta_seconds("2147483649").unwrap(), MAX_DELTA_SECONDS);
+        assert_eq!(
+            delta_seconds("99999999999999999999999").unwrap(),
+            MAX_DELTA_SECONDS
+        );
+        assert_eq!(
+            parse("max-age=4294967296").max_age,
+            Some(MAX_DELTA_SECONDS)
+        );
+    }
+
+    #[test]
+    fn unknown_extensions() {
+        let cc = parse("community=UCI, Foo, max-age=1");
+        assert_eq!(cc.max_age, Some(1));
+        assert_eq!(
+            cc.extensions(),
+            [
+                ("community".to_owned(), Some("UCI".to_owned())),
+                ("foo".to_owned(), None),
+            ]
+        );
+        assert_eq!(cc.to_string(), "max-age=1, community=UCI, foo");
+    }
+
+    #[test]
+    fn duplicate_directives_use_the_first() {
+        let cc = parse("max-age=10, public, max-age=20, ext=a, ext=b");
+        assert_eq!(cc.max_age, Some(10));
+        assert_eq!(cc.extensions(), [("ext".to_owned(), Some("a".to_owned()))]);
+        assert_eq!(cc.to_string(), "public, max-age=10, ext=a");
+
+        assert_eq!(parse("max-stale, max-stale=5").max_stale, Some(None));
+    }
+
+    #[test]
+    fn render_quotes_values() {
+        let cc = parse(r#"ext="a \"b\"", token="c""#);
+        assert_eq!(cc.to_string(), r#"ext="a \"b\"", token=c"#);
+    }
+
+    #[test]
+    fn round_trip() {
+        let all = CacheControl::new()
+            .public()
+            .private()
+            .no_cache()
+            .no_store()
+            .no_transform()
+            .must_revalidate()
+            .proxy_revalidate()
+            .must_understand()
+            .immutable()
+            .max_age(1)
+            .s_maxage(2)
+            .stale_while_revalidate(3)
+            .stale_if_error(4)
+            .max_stale(Some(5))
+            .min_fresh(6)
+            .only_if_cached();
+
+        for cc in [
+            CacheControl::new(),
+            CacheControl::new().public().max_age(60).must_revalidate(),
+            CacheControl::new().max_stale(None),
+            all,
+            parse(r#"private, ext="a, \"b\"", flag, tok=c"#),
+        ] {
+            assert_eq!(parse(&cc.to_string()), cc);
+
+            let value = cc.to_header_value().unwrap();
+            let decoded = CacheControl::decode(&mut std::iter::once(&value));
+            assert_eq!(decoded.unwrap(), cc);
+        }
+    }
+
+    #[test]
+    fn decode_multiple_headers() {
+        let values = [
+            HeaderValue::from_static("public"),
+            HeaderValue::from_static("max-age=60, ext=\"a,b\""),
+        ];
+        let cc = CacheControl::decode(&mut values.iter()).unwrap();
+        assert!(cc.public);
+        assert_eq!(cc.max_age, Some(60));
+        assert_eq!(
+            cc.extensions(),
+            [("ext".to_owned(), Some("a,b".to_owned()))]
+        );
+
+        assert!(CacheControl::decode(&mut std::iter::empty()).is_err());
+    }
+
+    #[test]
+    fn merge_self_takes_precedence() {
+        let handler = CacheControl::new().private().max_age(10);
+        let options = CacheControl::new()
+            .public()
+            .no_cache()
+            .must_revalidate()
+            .no_transform();
+
+        assert_eq!(
+            handler.merge(&options),
+            CacheControl::new().private().max_age(10).no_transform()
+        );
+    }
+
+    #[test]
+    fn merge_groups() {
+        // the visibility is taken from `other` if it isn't set
+        let cc = CacheControl::new().no_store();
+        let options = CacheControl::new().public().max_age(60);
+        assert_eq!(cc.merge(&options), CacheControl::new().public().no_store());
+
+        // the freshness is taken from `other` if it isn't set
+        let cc = CacheControl::new().public();
+        let options = CacheControl::new().private().max_age(60).immutable();
+        assert_eq!(
+            cc.merge(&options),
+            CacheControl::new().public().max_age(60).immutable()
+        );
+
+        // merging with nothing doesn't change the directives
+        let cc = CacheControl::new().public().max_age(60);
+        assert_eq!(cc.clone().merge(&CacheControl::new()), cc);
+        assert_eq!(CacheControl::new().merge(&cc), cc);
+    }
+
+    #[test]
+    fn merge_combines_the_rest() {
+        let cc = parse("max-stale=1, a=1, only-if-cached");
+        let other = parse("max-stale=2, min-fresh=3, a=2, b, must-understand");
+        let merged = cc.merge(&other);
+
+        assert_eq!(merged.max_stale, Some(Some(1)));
+        assert_eq!(merged.min_fresh, Some(3));
+        assert!(merged.only_if_cached);
+        assert!(merged.must_understand);
+        assert_eq!(
+            merged.extensions(),
+            [
+                ("a".to_owned(), Some("1".to_owned())),
+                ("b".to_owned(), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,561 @@
+//! Server-side in-memory cache of fully rendered responses.
+//!
+//! The pages of the site only change when the content is reloaded, so the
+//! rendered (and compressed) response of a `GET` request is stored and sent
+//! again for later requests instead of rendering the templates and
+//! compressing the body every time.
+//!
+//! Responses are stored per path (including the query) and per value of each
+//! request header listed in the `Vary` header of the response (e.g. a
+//! separate response for each `Accept-Encoding`).
+//!
+//! Paths that are served from files which can change without the content
+//! being reloaded (e.g. `/public/*`) should be skipped.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::{self, Body, Bytes},
+    headers::HeaderMapExt,
+    http::{
+        header, HeaderMap, HeaderName, HeaderValue, Method, Request,
+        StatusCode, Version,
+    },
+    response::{IntoResponse, Response},
+};
+use futures_util::future::{self, BoxFuture, FutureExt};
+use tokio::sync::watch;
+use tower::{Layer, Service};
+
+use super::{is_event_stream, matches_pattern, CacheControl};
+
+/// The limits of the cache.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// How long a response is stored for (default: 5 minutes).
+    pub ttl: Duration,
+    /// The total size of every stored response (default: 16 MiB).
+    pub max_bytes: usize,
+    /// The size of the largest response that will be stored (default: 1 MiB).
+    pub max_entry_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(5 * 60),
+            max_bytes: 16 * 1024 * 1024,
+            max_entry_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// A shared handle to the stored responses.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    store: Arc<Mutex<Store>>,
+    limits: Limits,
+}
+
+impl ResponseCache {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            store: Arc::default(),
+            limits,
+        }
+    }
+
+    /// Remove every stored response.
+    ///
+    /// Responses to requests that started before the cache was cleared are
+    /// not stored since they may have been rendered from the old content.
+    pub fn clear(&self) {
+        let mut store = self.lock();
+        store.entries.clear();
+        store.bytes = 0;
+        store.generation += 1;
+    }
+
+    /// The number of times the cache has been cleared.
+    fn generation(&self) -> u64 {
+        self.lock().generation
+    }
+
+    /// Clear the cache whenever the value of the channel changes (e.g. when
+    /// the content is reloaded).
+    ///
+    /// This spawns a task that runs until the sender is dropped.
+    pub fn clear_on_change<T>(&self, mut changes: watch::Receiver<T>)
+    where
+        T: Send + Sync + 'static,
+    {
+        let cache = self.clone();
+        tokio::spawn(async move {
+            while changes.changed().await.is_ok() {
+                cache.clear();
+                debug!("response cache cleared");
+            }
+        });
+    }
+
+    /// Find a stored response for the request.
+    fn get(&self, key: &str, request: &Request<Body>) -> Option<Response> {
+        let mut store = self.lock();
+        let now = Instant::now();
+
+        let variants = store.entries.get_mut(key)?;
+        let index = variants.iter().position(|e| e.matches(request))?;
+
+        if now.duration_since(variants[index].stored_at) > self.limits.ttl {
+            let entry = variants.swap_remove(index);
+            if variants.is_empty() {
+                store.entries.remove(key);
+            }
+            store.bytes -= entry.size;
+            return None;
+        }
+
+        let entry = &mut variants[index];
+        entry.last_used = now;
+        Some(entry.to_response())
+    }
+
+    /// Store the response, evicting the least recently used responses if the
+    /// cache is full.
+    ///
+    /// The response isn't stored if the cache has been cleared since the
+    /// request started (i.e. `generation` is out of date).
+    fn insert(&self, key: String, entry: Entry, generation: u64) {
+        if entry.size > self.limits.max_entry_bytes {
+            trace!("response for {} is too large to store", key);
+            return;
+        }
+
+        let mut store = self.lock();
+        if store.generation != generation {
+            trace!("response for {} is out of date", key);
+            return;
+        }
+
+        // replace the existing response for the same variant (if any)
+        let variants = store.entries.entry(key).or_default();
+        let replaced = variants
+            .iter()
+            .position(|e| e.vary == entry.vary)
+            .map(|i| variants.swap_remove(i).size)
+            .unwrap_or(0);
+        let size = entry.size;
+        variants.push(entry);
+        store.bytes = store.bytes - replaced + size;
+
+        while store.bytes > self.limits.max_bytes && store.evict_one() {}
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Store> {
+        // the store is always left in a valid state so a panic while it was
+        // locked can be ignored
+        self.store.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The stored responses.
+#[derive(Debug, Default)]
+struct Store {
+    /// Every variant of the response for each path.
+    entries: HashMap<String, Vec<Entry>>,
+    /// The total size of every stored response.
+    bytes: usize,
+    /// The number of times the store has been cleared.
+    generation: u64,
+}
+
+impl Store {
+    /// Remove the least recently used response.
+    fn evict_one(&mut self) -> bool {
+        let oldest = self
+            .entries
+            .iter()
+            .flat_map(|(key, variants)| {
+                variants.iter().enumerate().map(move |(i, e)| (key, i, e))
+            })
+            .min_by_key(|(_, _, e)| e.last_used)
+            .map(|(key, i, _)| (key.clone(), i));
+
+        let Some((key, index)) = oldest else {
+            return false;
+        };
+
+        if let Some(variants) = self.entries.get_mut(&key) {
+            let entry = variants.swap_remove(index);
+            self.bytes -= entry.size;
+            if variants.is_empty() {
+                self.entries.remove(&key);
+            }
+            trace!("evicted response for {}", key);
+        }
+
+        true
+    }
+}
+
+/// A stored response.
+#[derive(Debug)]
+struct Entry {
+    /// The request headers listed in the `Vary` header of the response along
+    /// with their values from the request that created the response.
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    body: Bytes,
+    /// The approximate memory used by the response.
+    size: usize,
+    stored_at: Instant,
+    last_used: Instant,
+}
+
+impl Entry {
+    /// Create an entry if the response can be stored.
+    ///
+    /// Responses that set cookies, vary on every request (`Vary: *`) or
+    /// aren't allowed to be stored (`Cache-Control: no-store` or `private`)
+    /// are never stored.
+    fn new(
+        request_headers: &HeaderMap,
+        parts: &axum::http::response::Parts,
+        body: Bytes,
+    ) -> Option<Self> {
+        let headers = &parts.headers;
+        if headers.contains_key(header::SET_COOKIE) {
+            return None;
+        }
+
+        match headers.typed_try_get::<CacheControl>() {
+            Ok(Some(cc)) if cc.no_store || cc.private => return None,
+            Ok(_) => {}
+            Err(_) => return None,
+        }
+
+        let mut vary = Vec::new();
+        for value in headers.get_all(header::VARY) {
+            for name in value.to_str().ok()?.split(',') {
+                let name = name.trim();
+                if name == "*" {
+                    return None;
+                }
+                if name.is_empty() {
+                    continue;
+                }
+                let name = HeaderName::try_from(name).ok()?;
+                let value = request_headers.get(&name).cloned();
+                vary.push((name, value));
+            }
+        }
+
+        let size = body.len()
+            + headers
+                .iter()
+                .map(|(k, v)| k.as_str().len() + v.len())
+                .sum::<usize>();
+        let now = Instant::now();
+
+        Some(Self {
+            vary,
+            status: parts.status,
+            version: parts.version,
+            headers: headers.clone(),
+            body,
+            size,
+            stored_at: now,
+            last_used: now,
+        })
+    }
+
+    /// Check if the request would create the same response.
+    fn matches(&self, request: &Request<Body>) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| request.headers().get(name) == value.as_ref())
+    }
+
+    /// Create a response from the stored response.
+    ///
+    /// No `Age` header is added, the stored response can be older than the
+    /// `max-age` of its `Cache-Control` header (the TTL is longer) which
+    /// would make clients treat it as stale straight away.
+    fn to_response(&self) -> Response {
+        let mut response =
+            Response::new(body::boxed(body::Full::from(self.body.clone())));
+        *response.status_mut() = self.status;
+        *response.version_mut() = self.version;
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
+}
+
+/// Store and re-use the responses to `GET` requests.
+#[derive(Debug, Clone)]
+pub struct ResponseCacheLayer {
+    cache: ResponseCache,
+    skip: Arc<Vec<String>>,
+}
+
+impl ResponseCacheLayer {
+    pub fn new(cache: ResponseCache) -> Self {
+        Self {
+            cache,
+            skip: Arc::default(),
+        }
+    }
+
+    /// Never store the responses for requests with a path matching the
+    /// pattern.
+    ///
+    /// The pattern is either an exact path (e.g. `/favicon.ico`) or a prefix
+    /// ending with `/*` (e.g. `/public/*`).
+    pub fn skip(mut self, pattern: &str) -> Self {
+        Arc::make_mut(&mut self.skip).push(pattern.to_owned());
+        self
+    }
+}
+
+impl<S> Layer<S> for ResponseCacheLayer {
+    type Service = ResponseCacheService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ResponseCacheService {
+            inner,
+            cache: self.cache.clone(),
+            skip: self.skip.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResponseCacheService<S> {
+    inner: S,
+    cache: ResponseCache,
+    skip: Arc<Vec<String>>,
+}
+
+impl<S> Service<Request<Body>> for ResponseCacheService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    #[instrument(skip_all)]
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // partial responses aren't stored so range requests skip the cache
+        let path = request.uri().path();
+        if request.method() != Method::GET
+            || request.headers().contains_key(header::RANGE)
+            || self.skip.iter().any(|p| matches_pattern(p, path))
+        {
+            return self.inner.call(request).boxed();
+        }
+
+        let key = request
+            .uri()
+            .path_and_query()
+            .map_or_else(|| request.uri().path(), |p| p.as_str())
+            .to_owned();
+
+        if let Some(response) = self.cache.get(&key, &request) {
+            trace!("response cache hit: {}", key);
+            return future::ready(Ok(response)).boxed();
+        }
+
+        // read before the response is rendered so that a response rendered
+        // from content that was replaced in the meantime isn't stored
+        let generation = self.cache.generation();
+        let request_headers = request.headers().clone();
+        let cache = self.cache.clone();
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = future.await?;
+
+            // only store complete responses, server-sent events never end
+            if response.status() != StatusCode::OK
+                || is_event_stream(response.headers())
+            {
+                return Ok(response);
+            }
+
+            let (parts, body) = response.into_parts();
+            let bytes = match hyper::body::to_bytes(body).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    error!("could not buffer response body: {:?}", e);
+                    return Ok(
+                        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                    );
+                }
+            };
+
+            if let Some(entry) =
+                Entry::new(&request_headers, &parts, bytes.clone())
+            {
+                trace!("response cache store: {}", key);
+                cache.insert(key, entry, generation);
+            }
+
+            Ok(Response::from_parts(
+                parts,
+                body::boxed(body::Full::from(bytes)),
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    /// Create an entry with a body of `size` bytes and no headers.
+    fn entry(size: usize) -> Entry {
+        let (parts, _) = Response::new(()).into_parts();
+        Entry::new(&HeaderMap::new(), &parts, Bytes::from(vec![b'a'; size]))
+            .unwrap()
+    }
+
+    fn request(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    fn limits(max_bytes: usize, max_entry_bytes: usize) -> Limits {
+        Limits {
+            ttl: Duration::from_secs(60),
+            max_bytes,
+            max_entry_bytes,
+        }
+    }
+
+    #[test]
+    fn expired_responses_are_removed() {
+        let cache = ResponseCache::new(Limits {
+            ttl: Duration::from_millis(10),
+            ..Default::default()
+        });
+        cache.insert("/".to_owned(), entry(10), 0);
+        assert!(cache.get("/", &request("/")).is_some());
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(cache.get("/", &request("/")).is_none());
+
+        // the path is removed along with its last response
+        let store = cache.lock();
+        assert!(store.entries.is_empty());
+        assert_eq!(store.bytes, 0);
+    }
+
+    #[test]
+    fn large_responses_are_not_stored() {
+        let cache = ResponseCache::new(limits(100, 10));
+        cache.insert("/small".to_owned(), entry(10), 0);
+        cache.insert("/large".to_owned(), entry(11), 0);
+
+        assert!(cache.get("/small", &request("/small")).is_some());
+        assert!(cache.get("/large", &request("/large")).is_none());
+        assert_eq!(cache.lock().bytes, 10);
+    }
+
+    #[test]
+    fn least_recently_used_responses_are_evicted() {
+        let cache = ResponseCache::new(limits(25, 10));
+        cache.insert("/a".to_owned(), entry(10), 0);
+        cache.insert("/b".to_owned(), entry(10), 0);
+
+        // use `/a` so that `/b` is the least recently used
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(cache.get("/a", &request("/a")).is_some());
+
+        cache.insert("/c".to_owned(), entry(10), 0);
+        assert!(cache.get("/a", &request("/a")).is_some());
+        assert!(cache.get("/b", &request("/b")).is_none());
+        assert!(cache.get("/c", &request("/c")).is_some());
+        assert_eq!(cache.lock().bytes, 20);
+    }
+
+    #[test]
+    fn replacing_a_response_keeps_the_size() {
+        let cache = ResponseCache::new(limits(100, 100));
+        cache.insert("/".to_owned(), entry(10), 0);
+        cache.insert("/".to_owned(), entry(20), 0);
+
+        let store = cache.lock();
+        assert_eq!(store.entries["/"].len(), 1);
+        assert_eq!(store.bytes, 20);
+    }
+
+    #[test]
+    fn responses_from_before_a_clear_are_not_stored() {
+        let cache = ResponseCache::new(Default::default());
+        let generation = cache.generation();
+        cache.clear();
+
+        cache.insert("/".to_owned(), entry(10), generation);
+        assert!(cache.get("/", &request("/")).is_none());
+
+        cache.insert("/".to_owned(), entry(10), cache.generation());
+        assert!(cache.get("/", &request("/")).is_some());
+    }
+
+    /// Send two requests and count how many times the handler is called.
+    async fn calls(layer: ResponseCacheLayer, uri: &str) -> usize {
+        let count = Arc::new(AtomicUsize::new(0));
+        let handler = {
+            let count = count.clone();
+            move || async move {
+                count.fetch_add(1, Ordering::Relaxed);
+                "Hello, world!"
+            }
+        };
+        let router = Router::new()
+            .route("/", get(handler.clone()))
+            .route("/public/main.css", get(handler))
+            .layer(layer);
+
+        for _ in 0..2 {
+            let response = router.clone().oneshot(request(uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        count.load(Ordering::Relaxed)
+    }
+
+    #[tokio::test]
+    async fn responses_are_reused() {
+        let cache = ResponseCache::new(Default::default());
+        let layer = ResponseCacheLayer::new(cache);
+        assert_eq!(calls(layer, "/").await, 1);
+    }
+
+    #[tokio::test]
+    async fn skipped_paths_are_not_stored() {
+        let cache = ResponseCache::new(Default::default());
+        let layer = ResponseCacheLayer::new(cache).skip("/public/*");
+        assert_eq!(calls(layer.clone(), "/public/main.css").await, 2);
+        assert_eq!(calls(layer, "/").await, 1);
+    }
+}
@@ -0,0 +1,235 @@
+//! Merge request header names into the `Vary` header of each response.
+//!
+//! A response may already vary on some request headers (e.g. `HX-Request`)
+//! so the names are appended to the existing header rather than replacing it.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Response};
+use pin_project_lite::pin_project;
+use tower::{Layer, Service};
+
+/// Add request header names to the `Vary` header of every response.
+#[derive(Debug, Clone)]
+pub struct VaryLayer {
+    names: Arc<[HeaderName]>,
+}
+
+impl VaryLayer {
+    pub fn new(names: impl IntoIterator<Item = HeaderName>) -> Self {
+        Self {
+            names: names.into_iter().collect(),
+        }
+    }
+}
+
+impl<S> Layer<S> for VaryLayer {
+    type Service = VaryService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        VaryService {
+            inner,
+            names: self.names.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VaryService<S> {
+    inner: S,
+    names: Arc<[HeaderName]>,
+}
+
+impl<S, Req, B> Service<Req> for VaryService<S>
+where
+    S: Service<Req, Response = Response<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = VaryFuture<S::Future>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Req) -> Self::Future {
+        VaryFuture {
+            future: self.inner.call(request),
+            names: self.names.clone(),
+        }
+    }
+}
+
+pin_project! {
+    /// The response future of `VaryService`.
+    pub struct VaryFuture<F> {
+        #[pin]
+        future: F,
+        names: Arc<[HeaderName]>,
+    }
+}
+
+impl<F, B, E> Future for VaryFuture<F>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut response = ready!(this.future.poll(cx))?;
+        merge_vary(response.headers_mut(), this.names.iter().cloned());
+        Poll::Ready(Ok(response))
+    }
+}
+
+/// Merge the header names into the `Vary` header.
+///
+/// The existing names are kept in order and the new names are appended if
+/// they aren't already listed (names are case-insensitive). If any name is
+/// `*` then the response varies on more than just the request headers so
+/// the header is set to only `*`.
+/// Source: https://www.rfc-editor.org/rfc/rfc9110#section-12.5.5
+pub fn merge_vary(
+    headers: &mut HeaderMap,
+    names: impl IntoIterator<Item = HeaderName>,
+) {
+    let mut merged: Vec<String> = Vec::new();
+
+    let existing = headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|v| match v.to_str() {
+            Ok(v) => Some(v.to_owned()),
+            Err(_) => {
+                warn!("ignoring invalid vary header: {:?}", v);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    let existing = existing.iter().flat_map(|v| v.split(','));
+    let names = names.into_iter().collect::<Vec<_>>();
+
+    for name in existing.chain(names.iter().map(|n| n.as_str())) {
+        let name = name.trim();
+        if name.is_empty()
+            || merged.iter().any(|m| m.eq_ignore_ascii_case(name))
+        {
+            continue;
+        }
+        merged.push(name.to_owned());
+    }
+
+    // an empty header is the same as no header
+    if merged.is_empty() {
+        headers.remove(header::VARY);
+        return;
+    }
+
+    let value = if merged.iter().any(|m| m == "*") {
+        HeaderValue::from_static("*")
+    } else {
+        // every name came from a valid header value or header name
+        match HeaderValue::from_str(&merged.join(", ")) {
+            Ok(value) => value,
+            Err(e) => {
+                error!("could not create vary header: {:?}", e);
+                return;
+            }
+        }
+    };
+
+    headers.insert(header::VARY, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Merge the names into the `Vary` headers and get the merged value.
+    fn merge(
+        existing: &[&'static str],
+        names: &[&'static str],
+    ) -> Option<String> {
+        let mut headers = HeaderMap::new();
+        for value in existing {
+            headers.append(header::VARY, HeaderValue::from_static(value));
+        }
+
+        merge_vary(
+            &mut headers,
+            names.iter().map(|n| HeaderName::from_static(n)),
+        );
+
+        let mut values = headers.get_all(header::VARY).iter();
+        let value = values.next().map(|v| v.to_str().unwrap().to_owned());
+        assert!(values.next().is_none(), "vary header is not merged");
+        value
+    }
+
+    #[test]
+    fn appends_names() {
+        assert_eq!(
+            merge(&[], &["accept-encoding", "hx-request"]).as_deref(),
+            Some("accept-encoding, hx-request")
+        );
+        assert_eq!(
+            merge(&["Cookie"], &["accept-encoding"]).as_deref(),
+            Some("Cookie, accept-encoding")
+        );
+    }
+
+    #[test]
+    fn removes_duplicates_ignoring_case() {
+        assert_eq!(
+            merge(&["Accept-Encoding, HX-Request"], &["accept-encoding"])
+                .as_deref(),
+            Some("Accept-Encoding, HX-Request")
+        );
+        assert_eq!(
+            merge(&[], &["hx-request", "hx-request"]).as_deref(),
+            Some("hx-request")
+        );
+    }
+
+    #[test]
+    fn merges_multiple_headers() {
+        assert_eq!(
+            merge(&["Cookie", " , Accept-Language ,", "cookie"], &["origin"])
+                .as_deref(),
+            Some("Cookie, Accept-Language, origin")
+        );
+    }
+
+    #[test]
+    fn star_absorbs_every_name() {
+        assert_eq!(
+            merge(&["Cookie, *"], &["accept-encoding"]).as_deref(),
+            Some("*")
+        );
+        assert_eq!(merge(&["Cookie"], &["*"]).as_deref(), Some("*"));
+    }
+
+    #[test]
+    fn nothing_to_merge() {
+        assert_eq!(merge(&[], &[]), None);
+        assert_eq!(merge(&[" , "], &[]), None);
+    }
+
+    #[test]
+    fn invalid_headers_are_ignored() {
+        let mut headers = HeaderMap::new();
+        headers
+            .append(header::VARY, HeaderValue::from_bytes(b"caf\xe9").unwrap());
+        headers.append(header::VARY, HeaderValue::from_static("Cookie"));
+
+        merge_vary(&mut headers, [header::ACCEPT_ENCODING]);
+        assert_eq!(headers[header::VARY], "Cookie, accept-encoding");
+    }
+}
@@ -0,0 +1,218 @@
+//! Runtime configuration.
+//!
+//! The configuration is loaded when the binary starts from (in order of
+//! precedence):
+//! 1. environment variables (e.g. `PORT=8080`)
+//! 2. an optional RON config file (default: `config.ron`, override with the
+//!    `CONFIG_FILE` environment variable)
+//! 3. the defaults
+//!
+//! Example `config.ron`:
+//!
+//! ```ron
+//! (
+//!     port: 8080,
+//!     base_url: "https://example.com",
+//!     mode: Production,
+//!     disallow: ["/dev/", "/drafts/"],
+//! )
+//! ```
+
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+
+/// The default config file, this is only used if it exists.
+const DEFAULT_CONFIG_FILE: &str = "config.ron";
+
+/// Configuration for the entire application.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The directory containing the posts and resume (env: `CONTENT_DIR`).
+    pub content_dir: PathBuf,
+    /// The directory containing static files (env: `PUBLIC_DIR`).
+    pub public_dir: PathBuf,
+    /// The address the server will listen on (env: `HOST`).
+    pub host: IpAddr,
+    /// The port the server will listen on (env: `PORT`).
+    pub port: u16,
+    /// The base URL of the site used to create absolute URLs, e.g. within
+    /// feeds and the sitemap (env: `BASE_URL`).
+    pub base_url: String,
+    /// The title of the site (env: `SITE_TITLE`).
+    pub title: String,
+    /// Whether the site is running in development or production
+    /// (env: `MODE`).
+    pub mode: Mode,
+    /// List hidden posts, i.e. files that start with `_`
+    /// (env: `DISABLE_FILTER=1`).
+    pub disable_filter: bool,
+    /// Paths that crawlers are not allowed to visit, these are listed in
+    /// `robots.txt` (config file only).
+    pub disallow: Vec<String>,
+}
+
+/// The mode the application is running in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum Mode {
+    /// Enables development only features (e.g. live reload).
+    Development,
+    Production,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            content_dir: "posts".into(),
+            public_dir: "public".into(),
+            host: [127, 0, 0, 1].into(),
+            port: 3000,
+            base_url: "http://127.0.0.1:3000".to_owned(),
+            title: "Liam Fenneman".to_owned(),
+            mode: Mode::default(),
+            disable_filter: false,
+            disallow: vec!["/dev/".to_owned()],
+        }
+    }
+}
+
+impl Default for Mode {
+    /// Debug builds default to development and release builds default to
+    /// production.
+    fn default() -> Self {
+        if cfg!(debug_assertions) {
+            Mode::Development
+        } else {
+            Mode::Production
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "development" | "dev" => Ok(Mode::Development),
+            "production" | "prod" => Ok(Mode::Production),
+            _ => {
+                bail!("unknown mode: {s} (expected development or production)")
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration from the config file and environment variables.
+    #[instrument]
+    pub fn load() -> anyhow::Result<Self> {
+        // an explicitly set config file must exist, however, the default
+        // config file is optional
+        let mut config = match std::env::var("CONFIG_FILE") {
+            Ok(path) => Self::from_file(Path::new(&path))?,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            Err(_) => Self::default(),
+        };
+
+        config.apply_env()?;
+
+        // avoid double slashes when joining the base URL with a path
+        config.base_url = config.base_url.trim_end_matches('/').to_owned();
+
+        debug!("config loaded: {:?}", config);
+
+        Ok(config)
+    }
+
+    /// Load the configuration from a RON file.
+    fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::read_to_string(path)
+            .with_context(|| format!("could not open config file {path:?}"))?;
+
+        trace!("open config file: {:?}", path);
+
+        ron::from_str(&file)
+            .with_context(|| format!("could not parse config file {path:?}"))
+    }
+
+    /// Override the configuration with any set environment variables.
+    fn apply_env(&mut self) -> anyhow::Result<()> {
+        if let Some(dir) = env("CONTENT_DIR")? {
+            self.content_dir = dir;
+        }
+        if let Some(dir) = env("PUBLIC_DIR")? {
+            self.public_dir = dir;
+        }
+        if let Some(host) = env("HOST")? {
+            self.host = host;
+        }
+        if let Some(port) = env("PORT")? {
+            self.port = port;
+        }
+        if let Some(url) = env("BASE_URL")? {
+            self.base_url = url;
+        }
+        if let Some(title) = env("SITE_TITLE")? {
+            self.title = title;
+        }
+        if let Some(mode) = env("MODE")? {
+            self.mode = mode;
+        }
+        if let Some(flag) = env::<String>("DISABLE_FILTER")? {
+            self.disable_filter = flag == "1";
+        }
+
+        Ok(())
+    }
+
+    /// The address the server should listen on.
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::from((self.host, self.port))
+    }
+
+    /// Check if the application is running in development mode.
+    pub fn is_development(&self) -> bool {
+        self.mode == Mode::Development
+    }
+
+    /// The directory containing the project posts.
+    pub fn projects_dir(&self) -> PathBuf {
+        self.content_dir.join("projects")
+    }
+
+    /// The directory containing the wishlist posts.
+    pub fn wishlist_dir(&self) -> PathBuf {
+        self.content_dir.join("wishlist")
+    }
+
+    /// The directory containing the blog posts.
+    pub fn blog_dir(&self) -> PathBuf {
+        self.content_dir.join("blog")
+    }
+
+    /// The resume RON file.
+    pub fn resume_file(&self) -> PathBuf {
+        self.content_dir.join("resume.ron")
+    }
+}
+
+/// Get and parse an environment variable if it is set.
+fn env<T>(key: &str) -> anyhow::Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    let Ok(value) = std::env::var(key) else {
+        return Ok(None);
+    };
+
+    match value.parse() {
+        Ok(value) => Ok(Some(value)),
+        Err(e) => bail!("could not parse {key}: {e}"),
+    }
+}
@@ -0,0 +1,169 @@
+//! In-memory content store.
+//!
+//! All of the content (projects, wishlist, blog, resume) is loaded from the
+//! `posts/` directory at startup. The markdown is pre-rendered into HTML so
+//! that the handlers only need to read from memory.
+//!
+//! The content can be reloaded at runtime (see `watcher`) which atomically
+//! swaps the old content with the new content. Any request that is currently
+//! being handled will continue to use the old content.
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use axum::{
+    extract::State,
+    headers::{HeaderMapExt, LastModified},
+    http::{header, Request},
+    middleware::Next,
+    response::Response,
+};
+use tokio::sync::watch;
+
+use crate::config::Config;
+use crate::posts::{Metadata, Post};
+use crate::{blog, projects, resume, search, wishlist};
+
+/// Every piece of content served by the site.
+#[derive(Debug, Clone)]
+pub struct Content {
+    /// All projects (including hidden ones) sorted by most recent first.
+    pub(crate) projects: Vec<Post<projects::Frontmatter, Metadata>>,
+    /// All visible wishlist posts sorted by most recent first.
+    pub(crate) wishlist: Vec<Post<wishlist::Frontmatter, Metadata>>,
+    /// All blog posts (including drafts) sorted by newest first.
+    pub(crate) blog: Vec<Post<blog::Frontmatter, Metadata>>,
+    /// The resume parsed from `posts/resume.ron`.
+    pub(crate) resume: resume::Resume,
+    /// An index of every project, wishlist and blog post.
+    pub(crate) search: search::Index,
+    /// When the content was loaded.
+    pub(crate) loaded_at: SystemTime,
+}
+
+impl Content {
+    /// Load and render all of the content from disk.
+    #[instrument(skip_all)]
+    pub fn load(config: &Config) -> anyhow::Result<Self> {
+        let projects = projects::section(config)
+            .load()
+            .context("failed to load projects")?;
+        debug!("{} projects loaded", projects.len());
+
+        let wishlist = wishlist::section(config)
+            .load()
+            .context("failed to load wishlist")?;
+        debug!("{} wishlist posts loaded", wishlist.len());
+
+        let blog = blog::section(config)
+            .load()
+            .context("failed to load blog")?;
+        debug!("{} blog posts loaded", blog.len());
+
+        let resume = resume::load(&config.resume_file())
+            .context("failed to load resume")?;
+        trace!("resume loaded");
+
+        let search = search::Index::new(
+            projects::search_documents(&projects)
+                .chain(wishlist::search_documents(&wishlist))
+                .chain(blog::search_documents(&blog))
+                .collect(),
+        );
+
+        Ok(Self {
+            projects,
+            wishlist,
+            blog,
+            resume,
+            search,
+            loaded_at: SystemTime::now(),
+        })
+    }
+
+    /// Create a `Last-Modified` header for the pages rendered from the
+    /// content.
+    ///
+    /// This is when the content was loaded rather than the dates of the posts
+    /// since a post can be edited without changing its dates, and the
+    /// templates can change whenever the site is deployed.
+    pub(crate) fn last_modified(&self) -> LastModified {
+        LastModified::from(self.loaded_at)
+    }
+}
+
+/// Set the `Last-Modified` header of every successful response to when the
+/// content was loaded (see `Content::last_modified`).
+///
+/// The time is read before the request is handled so that a response rendered
+/// from content which is replaced in the meantime isn't newer than it claims.
+/// Responses that already have the header (e.g. files from the public
+/// directory) and server-sent events are left as is.
+pub async fn set_last_modified<B>(
+    State(store): State<ContentStore>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let last_modified = store.get().last_modified();
+    let mut response = next.run(request).await;
+
+    let headers = response.headers();
+    if response.status().is_success()
+        && !headers.contains_key(header::LAST_MODIFIED)
+        && !crate::caching::is_event_stream(headers)
+    {
+        response.headers_mut().typed_insert(last_modified);
+    }
+
+    response
+}
+
+/// A shared handle to the current `Content`.
+///
+/// This uses a `watch` channel so that the content can be swapped atomically
+/// and so that other tasks can be notified when the content changes.
+#[derive(Debug, Clone)]
+pub struct ContentStore {
+    sender: Arc<watch::Sender<Arc<Content>>>,
+    config: Arc<Config>,
+}
+
+impl ContentStore {
+    /// Create a new store by loading all of the content from disk.
+    pub fn load(config: Arc<Config>) -> anyhow::Result<Self> {
+        let content = Content::load(&config)?;
+        let (sender, _) = watch::channel(Arc::new(content));
+        Ok(Self {
+            sender: Arc::new(sender),
+            config,
+        })
+    }
+
+    /// The configuration used to load the content.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Get the current content.
+    pub fn get(&self) -> Arc<Content> {
+        self.sender.borrow().clone()
+    }
+
+    /// Subscribe to changes of the content.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Content>> {
+        self.sender.subscribe()
+    }
+
+    /// Re-load all of the content from disk and swap it with the current
+    /// content.
+    ///
+    /// If the content fails to load then the current content is kept.
+    #[instrument(skip(self))]
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let content = Content::load(&self.config)?;
+        self.sender.send_replace(Arc::new(content));
+        info!("content reloaded");
+        Ok(())
+    }
+}
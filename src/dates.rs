@@ -0,0 +1,54 @@
+//! Dates used within the frontmatter of posts.
+//!
+//! Dates are written in the frontmatter as ISO 8601 calendar dates (e.g.
+//! `2023-10-29`) and are displayed in a human readable format (e.g.
+//! `29 October 2023`).
+
+use time::format_description::well_known::{Rfc2822, Rfc3339};
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime};
+
+/// The format used to display dates to the user.
+const HUMAN_FORMAT: &[FormatItem<'_>] =
+    format_description!("[day padding:none] [month repr:long] [year]");
+
+// Serde (de)serialisation of a `Date` in the `YYYY-MM-DD` format.
+//
+// Use with `#[serde(with = "crate::dates::iso")]` or for optional dates use
+// `#[serde(default, with = "crate::dates::iso::option")]`.
+time::serde::format_description!(pub iso, Date, "[year]-[month]-[day]");
+
+/// Format the date in a human readable format.
+pub fn human(date: Date) -> String {
+    date.format(HUMAN_FORMAT)
+        .expect("human date format is valid for all dates")
+}
+
+/// Format the date as an RFC 2822 date-time (used by RSS).
+///
+/// Dates don't have a time so midnight UTC is used.
+pub fn rfc2822(date: Date) -> String {
+    midnight(date)
+        .format(&Rfc2822)
+        .expect("RFC 2822 format is valid for all post dates")
+}
+
+/// Format the date as an RFC 3339 date-time (used by Atom and JSON Feed).
+///
+/// Dates don't have a time so midnight UTC is used.
+pub fn rfc3339(date: Date) -> String {
+    midnight(date)
+        .format(&Rfc3339)
+        .expect("RFC 3339 format is valid for all post dates")
+}
+
+/// Get the date-time at midnight UTC of the given date.
+fn midnight(date: Date) -> OffsetDateTime {
+    date.midnight().assume_utc()
+}
+
+/// Get the most recent of the created and (optional) updated dates.
+pub fn most_recent(created_at: Date, updated_at: Option<Date>) -> Date {
+    updated_at.map_or(created_at, |updated_at| updated_at.max(created_at))
+}
@@ -1,10 +1,29 @@
 //! Error handling for the entire application.
+//!
+//! Handlers return an `AppError` which is converted into a bare `500` response
+//! containing an `ErrorReport`. The `render_error_page` middleware then
+//! replaces the response with a rendered error page, since the middleware has
+//! access to the configuration:
+//! - in production the page only contains a short correlation ID which can be
+//!   matched with the full error in the logs
+//! - in development the page also contains the full error chain
+//!
+//! Missing pages respond with the rendered not found page instead (see
+//! `not_found_page`).
 
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+
+use askama::Template;
 use axum::{
+    extract::State,
     http::StatusCode,
     response::{IntoResponse, Response},
 };
 
+use crate::config::Config;
+
 /// A specialized `Result` type for the application.
 pub type Result<T> = std::result::Result<T, AppError>;
 
@@ -21,14 +40,97 @@ where
     }
 }
 
+/// Details about an error that are passed from the handler to the
+/// `render_error_page` middleware using the response extensions.
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    /// A short ID used to find the error within the logs.
+    pub id: String,
+    /// Each error in the chain, starting with the outermost error.
+    pub chain: Vec<String>,
+    /// The full debug output of the error (including the backtrace if it was
+    /// captured).
+    pub details: String,
+}
+
 // Convert `AppError` into an Axum response.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        // TODO: when in production don't return any message
-        (
+        let report = ErrorReport {
+            id: correlation_id(),
+            chain: self.0.chain().map(|e| e.to_string()).collect(),
+            details: format!("{:?}", self.0),
+        };
+
+        // always log the full error since the page shown in production
+        // doesn't contain any details
+        error!(id = report.id, "request failed: {:?}", self.0);
+
+        let mut response = (
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Something went wrong: {:?}", self.0),
+            format!("Something went wrong (reference: {})", report.id),
         )
-            .into_response()
+            .into_response();
+        response.extensions_mut().insert(report);
+        response
     }
 }
+
+#[derive(Template)]
+#[template(path = "pages/500.html")]
+struct ErrorPage<'a> {
+    config: &'a Config,
+    report: &'a ErrorReport,
+}
+
+#[derive(Template)]
+#[template(path = "pages/404.html")]
+struct NotFoundPage<'a> {
+    config: &'a Config,
+}
+
+/// Create a `404` response with the rendered not found page, used for unknown
+/// routes and for content that doesn't exist (e.g. an unknown tag).
+pub fn not_found_page(config: &Config) -> Response {
+    (StatusCode::NOT_FOUND, NotFoundPage { config }).into_response()
+}
+
+/// Replace responses created from an `AppError` with a rendered error page.
+pub async fn render_error_page(
+    State(config): State<Arc<Config>>,
+    response: Response,
+) -> Response {
+    let Some(report) = response.extensions().get::<ErrorReport>() else {
+        return response;
+    };
+
+    let page = ErrorPage {
+        config: &config,
+        report,
+    };
+
+    match page.render() {
+        Ok(html) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            axum::response::Html(html),
+        )
+            .into_response(),
+        Err(e) => {
+            // fall back to the plain text response
+            error!(id = report.id, "could not render error page: {:?}", e);
+            response
+        }
+    }
+}
+
+/// Create a short random ID (8 hex characters) for an error.
+fn correlation_id() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    format!("{:08x}", hasher.finish() as u32)
+}
@@ -0,0 +1,280 @@
+//! Static site export.
+//!
+//! Every route served by the router is rendered into an output directory along
+//! with the `public/` directory. The output can then be deployed to any static
+//! file host.
+//!
+//! Pages are rendered by sending a request through the router itself so that
+//! the exported site is identical to what the server would respond with.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use tower::ServiceExt;
+
+use crate::config::Config;
+use crate::content::Content;
+
+/// The default directory that the site is exported to.
+pub const DEFAULT_OUT_DIR: &str = "dist";
+
+/// The routes that are always exported (excluding posts).
+const ROUTES: [&str; 11] = [
+    "/",
+    "/resume",
+    "/projects",
+    "/projects/feed.xml",
+    "/projects/atom.xml",
+    "/projects/feed.json",
+    "/wishlist",
+    "/blog",
+    "/tags",
+    "/sitemap.xml",
+    "/robots.txt",
+];
+
+/// A route that is never served so the router responds with the 404 page.
+const NOT_FOUND_ROUTE: &str = "/404";
+
+/// A file written to the output directory to mark it as created by an export.
+///
+/// The output directory is only removed if it has this file (or is empty) so
+/// that exporting to the wrong path can't delete anything else.
+const MARKER: &str = ".export";
+
+/// Export the entire site into the `out` directory.
+#[instrument(skip(router, config, content))]
+pub async fn export(
+    router: Router,
+    config: &Config,
+    content: &Content,
+    out: &Path,
+) -> anyhow::Result<()> {
+    clean(out)?;
+    write(&out.join(MARKER), b"")?;
+
+    for route in routes(content) {
+        let route = route.as_str();
+        let (status, html) = render(&router, route).await?;
+        if !status.is_success() {
+            bail!("route {route} responded with {status}");
+        }
+
+        write(&out.join(route_to_file(route)), &html)?;
+        info!("exported {}", route);
+    }
+
+    // searching needs the server so the exported search page only explains
+    // that search isn't available
+    let html = crate::search::export_page(config)
+        .context("could not render the search page")?;
+    write(&out.join(route_to_file("/search")), html.as_bytes())?;
+    info!("exported search page");
+
+    // the router responds to unknown routes with the 404 page, most static
+    // hosts will serve `404.html` when a file can't be found
+    let (_, html) = render(&router, NOT_FOUND_ROUTE).await?;
+    write(&out.join("404.html"), &html)?;
+    info!("exported 404 page");
+
+    copy_dir(&config.public_dir, &out.join("public"))?;
+    std::fs::copy(
+        config.public_dir.join("favicon.ico"),
+        out.join("favicon.ico"),
+    )
+    .context("could not copy favicon")?;
+    info!("exported public files");
+
+    Ok(())
+}
+
+/// Every route that is exported (excluding the 404 page).
+fn routes(content: &Content) -> Vec<String> {
+    // hidden projects and drafts (and tags only used by them) are not
+    // exported since they can't be found without knowing the URL
+    let projects = content
+        .projects
+        .iter()
+        .filter(|p| !p.metadata.hidden)
+        .map(|p| p.metadata.slug.clone());
+    let blog = crate::blog::published(&content.blog)
+        .map(|p| p.metadata.slug.clone())
+        .collect::<Vec<_>>();
+    // the first page is already exported as `/blog`
+    let blog_pages =
+        (2..=crate::blog::page_count(blog.len())).map(crate::blog::page_path);
+    let tags = crate::tags::tags(&crate::tags::entries(content, false))
+        .into_iter()
+        .map(|t| format!("/tags/{}", t.slug));
+
+    ROUTES
+        .into_iter()
+        .map(str::to_owned)
+        .chain(projects)
+        .chain(blog)
+        .chain(blog_pages)
+        .chain(tags)
+        .collect()
+}
+
+/// Remove the output directory left by a previous export.
+///
+/// Fails if the path isn't a directory or if it has files but isn't marked as
+/// created by an export.
+fn clean(out: &Path) -> anyhow::Result<()> {
+    if !out.exists() {
+        return Ok(());
+    }
+
+    if !out.is_dir() {
+        bail!("{out:?} is not a directory");
+    }
+
+    let is_empty = std::fs::read_dir(out)
+        .with_context(|| format!("could not read {out:?}"))?
+        .next()
+        .is_none();
+    if !is_empty && !out.join(MARKER).is_file() {
+        bail!(
+            "{out:?} is not empty and was not created by an export, remove it \
+             or choose a different directory"
+        );
+    }
+
+    std::fs::remove_dir_all(out)
+        .with_context(|| format!("could not remove {out:?}"))
+}
+
+/// Render a route by sending a `GET` request through the router.
+async fn render(
+    router: &Router,
+    route: &str,
+) -> anyhow::Result<(StatusCode, Vec<u8>)> {
+    let request = Request::get(route).body(Body::empty())?;
+    let response = router.clone().oneshot(request).await?;
+    let status = response.status();
+
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .with_context(|| format!("could not read body of route {route}"))?;
+
+    Ok((status, body.to_vec()))
+}
+
+/// Get the file a route should be written to.
+///
+/// Each page is written to an `index.html` within a directory of the same
+/// name so that the URLs don't change (e.g. `/projects` is written to
+/// `projects/index.html`). Routes with a file extension (e.g. feeds) are
+/// written to that file.
+fn route_to_file(route: &str) -> PathBuf {
+    let path = Path::new(route.trim_start_matches('/'));
+    if path.extension().is_some() {
+        return path.to_owned();
+    }
+
+    path.join("index.html")
+}
+
+/// Write a file, creating any of the parent directories.
+fn write(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("could not create {parent:?}"))?;
+    }
+
+    std::fs::write(path, contents)
+        .with_context(|| format!("could not write {path:?}"))
+}
+
+/// Recursively copy a directory.
+fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(to)
+        .with_context(|| format!("could not create {to:?}"))?;
+
+    for entry in std::fs::read_dir(from)
+        .with_context(|| format!("could not read {from:?}"))?
+    {
+        let entry = entry?;
+        let path = entry.path();
+        let target = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&path, &target)?;
+        } else {
+            std::fs::copy(&path, &target)
+                .with_context(|| format!("could not copy {path:?}"))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write a post with the frontmatter into the directory, each post is
+    /// tagged with its file name so that tags only used by hidden posts and
+    /// drafts can be checked too.
+    fn post(dir: &Path, file: &str, frontmatter: &str) {
+        let post = format!("---\n{frontmatter}\ntags: [{file}]\n---\n\nText.");
+        write(&dir.join(file), post.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn hidden_posts_and_drafts_are_not_listed() {
+        let dir = std::env::temp_dir()
+            .join(format!("personal-site-export-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let project = "name: Project\ndescription: A project.\n\
+                       created_at: 2023-10-29";
+        post(&dir.join("projects"), "visible.md", project);
+        post(&dir.join("projects"), "_hidden.md", project);
+
+        let blog = "title: Post\nsummary: A post.\ndate: 2023-11-05";
+        post(&dir.join("blog"), "published.md", blog);
+        post(&dir.join("blog"), "_hidden.md", blog);
+        post(
+            &dir.join("blog"),
+            "draft.md",
+            &format!("{blog}\ndraft: true"),
+        );
+
+        std::fs::create_dir_all(dir.join("wishlist")).unwrap();
+        std::fs::copy("posts/resume.ron", dir.join("resume.ron")).unwrap();
+
+        // the filter is disabled so the hidden posts and drafts are loaded
+        let config = Config {
+            content_dir: dir.clone(),
+            disable_filter: true,
+            ..Default::default()
+        };
+        let content = Content::load(&config);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let content = content.unwrap();
+        assert_eq!(content.projects.len(), 2);
+        assert_eq!(content.blog.len(), 3);
+
+        let sitemap = crate::sitemap::urls(&content)
+            .into_iter()
+            .map(|url| url.path)
+            .collect::<Vec<_>>();
+        for routes in [sitemap, routes(&content)] {
+            assert!(routes.contains(&"/projects/visible".to_owned()));
+            assert!(routes.contains(&"/blog/published".to_owned()));
+            assert!(routes.contains(&"/tags/visible-md".to_owned()));
+
+            let hidden = routes
+                .iter()
+                .filter(|r| r.contains("hidden") || r.contains("draft"));
+            assert_eq!(hidden.count(), 0, "{routes:?}");
+        }
+    }
+}
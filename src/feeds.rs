@@ -0,0 +1,149 @@
+//! Syndication feeds (RSS, Atom and JSON Feed).
+//!
+//! A `Feed` is created from a list of posts and can then be rendered into any
+//! of the supported formats. The XML formats are rendered using Askama
+//! templates and the JSON Feed is serialised using `serde_json`.
+//!
+//! Specifications:
+//! - RSS 2.0: https://www.rssboard.org/rss-specification
+//! - Atom: https://datatracker.ietf.org/doc/html/rfc4287
+//! - JSON Feed 1.1: https://www.jsonfeed.org/version/1.1/
+
+use askama::Template;
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+use time::Date;
+
+use crate::filters;
+
+/// Should the rendered HTML content of each post be included in the feeds.
+///
+/// If this is `false` then only the description is included.
+pub const INCLUDE_CONTENT: bool = true;
+
+/// The format to render the feed as.
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    /// RSS 2.0 (`application/rss+xml`).
+    Rss,
+    /// Atom (`application/atom+xml`).
+    Atom,
+    /// JSON Feed 1.1 (`application/feed+json`).
+    Json,
+}
+
+/// A feed of posts.
+#[derive(Debug, Clone)]
+pub struct Feed<'a> {
+    /// The base URL used to create absolute URLs.
+    pub base_url: &'a str,
+    /// The title of the feed.
+    pub title: String,
+    /// A short description of the feed.
+    pub description: &'a str,
+    /// The path of the page listing the posts (e.g. `/projects`).
+    pub path: &'a str,
+    /// Each of the posts within the feed, most recent first.
+    pub entries: Vec<Entry<'a>>,
+}
+
+/// A single post within a `Feed`.
+#[derive(Debug, Clone)]
+pub struct Entry<'a> {
+    /// The title of the post.
+    pub title: &'a str,
+    /// A short description of the post.
+    pub summary: &'a str,
+    /// The path of the post (e.g. `/projects/personal-site`).
+    pub path: &'a str,
+    /// The date the post was created.
+    pub published: Date,
+    /// The date the post was last updated.
+    pub updated: Option<Date>,
+    /// The rendered HTML content of the post.
+    pub content: Option<String>,
+}
+
+impl Entry<'_> {
+    /// The most recent of either the published or updated date.
+    fn most_recent(&self) -> Date {
+        crate::dates::most_recent(self.published, self.updated)
+    }
+}
+
+impl Feed<'_> {
+    /// The most recent date any of the entries were published or updated.
+    ///
+    /// If there are no entries then today's date is used.
+    fn updated(&self) -> Date {
+        self.entries
+            .iter()
+            .map(Entry::most_recent)
+            .max()
+            .unwrap_or_else(|| time::OffsetDateTime::now_utc().date())
+    }
+
+    /// Render the feed into a response of the given format.
+    #[instrument(skip(self), fields(path = self.path))]
+    pub fn render(&self, format: Format) -> crate::error::Result<Response> {
+        let (content_type, body) = match format {
+            Format::Rss => {
+                ("application/rss+xml", RssFeed { feed: self }.render()?)
+            }
+            Format::Atom => {
+                ("application/atom+xml", AtomFeed { feed: self }.render()?)
+            }
+            Format::Json => (
+                "application/feed+json",
+                serde_json::to_string(&self.json())?,
+            ),
+        };
+
+        trace!("render {:?} feed", format);
+
+        Ok(([(header::CONTENT_TYPE, content_type)], body).into_response())
+    }
+
+    /// Create the JSON Feed representation of the feed.
+    fn json(&self) -> serde_json::Value {
+        let items = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let url = format!("{}{}", self.base_url, entry.path);
+                serde_json::json!({
+                    "id": url,
+                    "url": url,
+                    "title": entry.title,
+                    "summary": entry.summary,
+                    "content_html": entry.content.as_deref().unwrap_or(entry.summary),
+                    "date_published": crate::dates::rfc3339(entry.published),
+                    "date_modified": crate::dates::rfc3339(entry.most_recent()),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::json!({
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": self.title,
+            "description": self.description,
+            "home_page_url": format!("{}{}", self.base_url, self.path),
+            "feed_url": format!("{}{}/feed.json", self.base_url, self.path),
+            "items": items,
+        })
+    }
+}
+
+#[derive(Template)]
+#[template(path = "feeds/rss.xml")]
+struct RssFeed<'a> {
+    feed: &'a Feed<'a>,
+}
+
+#[derive(Template)]
+#[template(path = "feeds/atom.xml")]
+struct AtomFeed<'a> {
+    feed: &'a Feed<'a>,
+}
@@ -0,0 +1,29 @@
+//! Custom filters for the Askama templates.
+//!
+//! Askama looks for custom filters in a `filters` module in scope of the
+//! template, so any module with a template that uses these filters must
+//! `use crate::filters;`.
+
+use std::borrow::Borrow;
+
+use time::Date;
+
+/// Format a date in a human readable format (e.g. `29 October 2023`).
+pub fn date<D: Borrow<Date>>(date: &D) -> askama::Result<String> {
+    Ok(crate::dates::human(*date.borrow()))
+}
+
+/// Format a date as an RFC 2822 date-time (e.g. for RSS feeds).
+pub fn rfc2822<D: Borrow<Date>>(date: &D) -> askama::Result<String> {
+    Ok(crate::dates::rfc2822(*date.borrow()))
+}
+
+/// Create the slug of a tag (e.g. `Home Lab` -> `home-lab`).
+pub fn slug<S: AsRef<str>>(tag: &S) -> askama::Result<String> {
+    Ok(crate::posts::slug(tag.as_ref()))
+}
+
+/// Format a date as an RFC 3339 date-time (e.g. for Atom feeds).
+pub fn rfc3339<D: Borrow<Date>>(date: &D) -> askama::Result<String> {
+    Ok(crate::dates::rfc3339(*date.borrow()))
+}
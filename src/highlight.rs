@@ -0,0 +1,90 @@
+//! Syntax highlighting for fenced code blocks.
+//!
+//! Code blocks are highlighted when the posts are rendered into HTML so no
+//! JavaScript is needed. Each token is wrapped in a `<span>` with its
+//! TextMate scope as classes (e.g. `keyword.control.rust` is
+//! `hl-keyword hl-control hl-rust`) which are coloured by the stylesheet.
+//!
+//! The syntaxes come from `two-face` (the syntaxes bundled with `bat`) since
+//! the syntect defaults don't include TOML, Nix or TypeScript.
+
+use std::sync::OnceLock;
+
+use syntect::html::{ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+/// The prefix of every class so they don't clash with the other styles.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// Languages which aren't the name or file extension of a syntax.
+const ALIASES: [(&str, &str); 3] =
+    [("shell", "bash"), ("zsh", "bash"), ("console", "bash")];
+
+/// Every syntax which can be highlighted.
+///
+/// Loading the syntaxes is slow so it is only done once.
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(two_face::syntax::extra_newlines)
+}
+
+/// Highlight the code, returning `None` if the language isn't supported.
+pub fn highlight(code: &str, language: &str) -> Option<String> {
+    let language = ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(language))
+        .map_or(language, |(_, language)| language);
+
+    let syntaxes = syntaxes();
+    let syntax = syntaxes.find_syntax_by_token(language)?;
+    let mut html = ClassedHTMLGenerator::new_with_class_style(
+        syntax,
+        syntaxes,
+        CLASS_STYLE,
+    );
+
+    for line in LinesWithEndings::from(code) {
+        if let Err(e) = html.parse_html_for_line_which_includes_newline(line) {
+            warn!("could not highlight {} code: {}", language, e);
+            return None;
+        }
+    }
+
+    Some(html.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn languages_are_highlighted() {
+        let cases = [
+            ("rust", "fn main() {}"),
+            ("nix", "{ pkgs, ... }: { }"),
+            ("toml", "[package]\nname = \"site\""),
+            ("yaml", "name: site"),
+            ("bash", "echo \"hello\""),
+            ("typescript", "const a: number = 1;"),
+            ("sql", "SELECT * FROM posts;"),
+            // aliases and file extensions
+            ("shell", "echo \"hello\""),
+            ("sh", "echo \"hello\""),
+            ("ts", "const a: number = 1;"),
+        ];
+
+        for (language, code) in cases {
+            let html = highlight(code, language);
+            assert!(
+                html.is_some_and(|html| html.contains("<span class=\"hl-")),
+                "{language:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_languages_are_not_highlighted() {
+        assert_eq!(highlight("<b>", "not-a-language"), None);
+    }
+}
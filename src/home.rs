@@ -1,10 +1,27 @@
+use std::sync::Arc;
+
 use askama::Template;
-use axum::{routing::get, Router};
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use crate::config::Config;
+use crate::AppState;
 
 #[derive(Template)]
 #[template(path = "pages/index.html")]
-struct HomePage;
+struct HomePage<'a> {
+    config: &'a Config,
+}
+
+#[instrument(skip_all)]
+async fn get_home(State(config): State<Arc<Config>>) -> Response {
+    HomePage { config: &config }.into_response()
+}
 
-pub fn router() -> Router {
-    Router::new().route("/", get(async || HomePage))
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", get(get_home))
 }
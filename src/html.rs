@@ -0,0 +1,812 @@
+//! Render the Markdown AST of a post into HTML.
+//!
+//! `markdown` can only render HTML straight from the Markdown text, so any
+//! changes to the HTML (e.g. the `id` of each heading) would have to search
+//! the rendered HTML for the right tags. Rendering the AST means each change
+//! is made to the node it belongs to instead.
+//!
+//! The HTML is the same as `markdown` renders with the GitHub flavored
+//! markdown options: raw HTML is escaped, unsafe URLs are removed and the
+//! footnotes are rendered in a section at the end.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use markdown::mdast::{
+    AlignKind, BlockQuote, Code, Definition, FootnoteDefinition, Heading, List,
+    ListItem, Node, Table,
+};
+
+use crate::{alerts, posts};
+
+/// The protocols allowed in the `href` of a link, any other protocol (e.g.
+/// `javascript:`) is removed.
+const SAFE_PROTOCOL_HREF: [&str; 6] =
+    ["http", "https", "irc", "ircs", "mailto", "xmpp"];
+
+/// The protocols allowed in the `src` of an image.
+const SAFE_PROTOCOL_SRC: [&str; 2] = ["http", "https"];
+
+/// Where a link within a post goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkKind {
+    /// Another site (e.g. `https://github.com`), these open in a new tab.
+    External,
+    /// Another page on this site (e.g. `/projects`), these are boosted by
+    /// htmx like every other link on the site.
+    Internal,
+    /// A heading within the same page (e.g. `#overview`), these aren't
+    /// boosted so the browser scrolls to the heading.
+    Anchor,
+}
+
+impl LinkKind {
+    /// Classify the destination of a link.
+    fn of(url: &str) -> Self {
+        if url.starts_with('#') {
+            return LinkKind::Anchor;
+        }
+
+        // protocol relative URLs (e.g. `//example.com`) are always external
+        if url.starts_with("//") {
+            return LinkKind::External;
+        }
+
+        // a scheme (e.g. `https:` or `mailto:`) must come before any path,
+        // query or fragment
+        let end = url.find(['/', '?', '#']).unwrap_or(url.len());
+        match url[..end].find(':') {
+            Some(_) => LinkKind::External,
+            None => LinkKind::Internal,
+        }
+    }
+
+    /// The attributes added to the `<a>` tag of the link.
+    fn attributes(self) -> &'static str {
+        match self {
+            LinkKind::External => {
+                " target=\"_blank\" rel=\"noopener noreferrer\""
+            }
+            LinkKind::Internal => "",
+            LinkKind::Anchor => " hx-boost=\"false\"",
+        }
+    }
+}
+
+/// Render the Markdown AST into HTML.
+///
+/// Each heading in `toc` is given its anchor as an `id` along with a
+/// permalink to the heading. Links are given the attributes for their kind
+/// (e.g. external links open in a new tab) and alerts are rendered with
+/// their title and icon.
+///
+/// The `id` of each footnote starts with `id_prefix` so that the footnotes of
+/// posts rendered on the same page don't clash, the anchors in `toc` are
+/// expected to be prefixed already.
+pub fn render(ast: &Node, toc: &[posts::Heading], id_prefix: &str) -> String {
+    let mut renderer = Renderer {
+        html: String::new(),
+        id_prefix,
+        anchors: toc
+            .iter()
+            .map(|heading| (heading.offset, heading.anchor.as_str()))
+            .collect(),
+        definitions: HashMap::new(),
+        footnotes: HashMap::new(),
+        calls: Vec::new(),
+    };
+    renderer.collect(ast);
+    renderer.block(ast);
+    renderer.footnote_section();
+    renderer.html
+}
+
+struct Renderer<'a> {
+    html: String,
+    /// Added to the start of the `id` of each footnote.
+    id_prefix: &'a str,
+    /// The anchor of each heading keyed by the offset of the heading within
+    /// the Markdown.
+    anchors: HashMap<usize, &'a str>,
+    /// The definitions of reference links and images keyed by identifier.
+    definitions: HashMap<&'a str, &'a Definition>,
+    /// The footnote definitions keyed by identifier.
+    footnotes: HashMap<&'a str, &'a FootnoteDefinition>,
+    /// The identifier of each referenced footnote and the number of
+    /// references to it, in the order they are first referenced.
+    calls: Vec<(&'a str, usize)>,
+}
+
+impl<'a> Renderer<'a> {
+    /// Collect every definition within the AST, the first definition of an
+    /// identifier is used.
+    fn collect(&mut self, node: &'a Node) {
+        match node {
+            Node::Definition(definition) => {
+                self.definitions
+                    .entry(&definition.identifier)
+                    .or_insert(definition);
+            }
+            Node::FootnoteDefinition(definition) => {
+                self.footnotes
+                    .entry(&definition.identifier)
+                    .or_insert(definition);
+            }
+            _ => {}
+        }
+
+        for child in node.children().into_iter().flatten() {
+            self.collect(child);
+        }
+    }
+
+    /// Start a new line unless already at the start of one.
+    fn line(&mut self) {
+        if !self.html.is_empty() && !self.html.ends_with('\n') {
+            self.html.push('\n');
+        }
+    }
+
+    fn blocks(&mut self, nodes: &'a [Node]) {
+        for node in nodes {
+            self.block(node);
+        }
+    }
+
+    fn inlines(&mut self, nodes: &'a [Node]) {
+        for node in nodes {
+            self.inline(node);
+        }
+    }
+
+    /// Render a node that is within a container (e.g. a blockquote), each
+    /// of these starts on a new line.
+    fn block(&mut self, node: &'a Node) {
+        match node {
+            Node::Root(root) => self.blocks(&root.children),
+            Node::Paragraph(paragraph) => {
+                self.line();
+                self.html.push_str("<p>");
+                self.inlines(&paragraph.children);
+                self.html.push_str("</p>");
+            }
+            Node::Heading(heading) => self.heading(heading),
+            Node::BlockQuote(blockquote) => self.blockquote(blockquote),
+            Node::List(list) => self.list(list),
+            Node::Code(code) => self.code(code),
+            Node::Table(table) => self.table(table),
+            Node::ThematicBreak(_) => {
+                self.line();
+                self.html.push_str("<hr />");
+            }
+            Node::Html(html) => {
+                self.line();
+                self.html.push_str(&escape(&html.value));
+            }
+            // definitions are only used by references and the frontmatter
+            // has already been parsed
+            Node::Definition(_)
+            | Node::FootnoteDefinition(_)
+            | Node::Yaml(_)
+            | Node::Toml(_) => {}
+            node => self.inline(node),
+        }
+    }
+
+    /// Render a node that is within a paragraph, heading or table cell.
+    fn inline(&mut self, node: &'a Node) {
+        match node {
+            Node::Text(text) => self.html.push_str(&escape(&text.value)),
+            Node::Emphasis(emphasis) => {
+                self.html.push_str("<em>");
+                self.inlines(&emphasis.children);
+                self.html.push_str("</em>");
+            }
+            Node::Strong(strong) => {
+                self.html.push_str("<strong>");
+                self.inlines(&strong.children);
+                self.html.push_str("</strong>");
+            }
+            Node::Delete(delete) => {
+                self.html.push_str("<del>");
+                self.inlines(&delete.children);
+                self.html.push_str("</del>");
+            }
+            Node::InlineCode(code) => {
+                self.html.push_str("<code>");
+                self.html.push_str(&escape(&inline_code(&code.value)));
+                self.html.push_str("</code>");
+            }
+            Node::Break(_) => self.html.push_str("<br />\n"),
+            Node::Html(html) => self.html.push_str(&escape(&html.value)),
+            Node::Link(link) => {
+                self.link(&link.url, link.title.as_deref(), &link.children)
+            }
+            Node::LinkReference(reference) => {
+                match self.definitions.get(reference.identifier.as_str()) {
+                    Some(definition) => self.link(
+                        &definition.url,
+                        definition.title.as_deref(),
+                        &reference.children,
+                    ),
+                    None => self.inlines(&reference.children),
+                }
+            }
+            Node::Image(image) => {
+                self.image(&image.url, image.title.as_deref(), &image.alt);
+            }
+            Node::ImageReference(reference) => {
+                match self.definitions.get(reference.identifier.as_str()) {
+                    Some(definition) => self.image(
+                        &definition.url,
+                        definition.title.as_deref(),
+                        &reference.alt,
+                    ),
+                    None => self.html.push_str(&escape(&reference.alt)),
+                }
+            }
+            Node::FootnoteReference(reference) => {
+                self.footnote_reference(&reference.identifier);
+            }
+            node => {
+                for child in node.children().into_iter().flatten() {
+                    self.inline(child);
+                }
+            }
+        }
+    }
+
+    fn heading(&mut self, heading: &'a Heading) {
+        let level = heading.depth;
+        let anchor = heading
+            .position
+            .as_ref()
+            .and_then(|position| self.anchors.get(&position.start.offset))
+            .copied();
+
+        self.line();
+        let Some(anchor) = anchor else {
+            let _ = write!(self.html, "<h{level}>");
+            self.inlines(&heading.children);
+            let _ = write!(self.html, "</h{level}>");
+            return;
+        };
+
+        // writing to a `String` can't fail
+        let _ = write!(self.html, "<h{level} id=\"{anchor}\">");
+        self.inlines(&heading.children);
+        let _ = write!(
+            self.html,
+            "<a class=\"anchor\" href=\"#{anchor}\" hx-boost=\"false\" \
+             aria-label=\"Permalink\">#</a></h{level}>"
+        );
+    }
+
+    /// Render a blockquote, blockquotes that start with the marker of an
+    /// alert are rendered as an alert.
+    fn blockquote(&mut self, blockquote: &'a BlockQuote) {
+        self.line();
+        let Some(alert) = alerts::find(blockquote) else {
+            self.html.push_str("<blockquote>");
+            self.blocks(&blockquote.children);
+            self.line();
+            self.html.push_str("</blockquote>");
+            return;
+        };
+
+        // the marker is replaced by the title so the first paragraph is only
+        // rendered if there is anything after the marker
+        self.html.push_str(&alerts::start(alert.kind));
+        if !alert.text.is_empty() || !alert.inlines.is_empty() {
+            self.line();
+            self.html.push_str("<p>");
+            self.html.push_str(&escape(alert.text));
+            self.inlines(alert.inlines);
+            self.html.push_str("</p>");
+        }
+        self.blocks(alert.blocks);
+        self.line();
+        self.html.push_str("</blockquote>");
+    }
+
+    fn list(&mut self, list: &'a List) {
+        let tag = if list.ordered { "ol" } else { "ul" };
+
+        self.line();
+        match list.start {
+            Some(start) if list.ordered && start != 1 => {
+                let _ = write!(self.html, "<{tag} start=\"{start}\">");
+            }
+            _ => {
+                let _ = write!(self.html, "<{tag}>");
+            }
+        }
+
+        // paragraphs within a tight list (i.e. without blank lines between
+        // or within the items) aren't wrapped in `<p>`
+        let tight = !list.spread
+            && list.children.iter().all(|item| match item {
+                Node::ListItem(item) => !item.spread,
+                _ => true,
+            });
+        for item in &list.children {
+            match item {
+                Node::ListItem(item) => self.list_item(item, tight),
+                node => self.block(node),
+            }
+        }
+
+        self.line();
+        let _ = write!(self.html, "</{tag}>");
+    }
+
+    fn list_item(&mut self, item: &'a ListItem, tight: bool) {
+        let mut checkbox = item.checked.map(|checked| {
+            if checked {
+                "<input type=\"checkbox\" disabled=\"\" checked=\"\" /> "
+            } else {
+                "<input type=\"checkbox\" disabled=\"\" /> "
+            }
+        });
+
+        self.line();
+        self.html.push_str("<li>");
+        if !matches!(item.children.first(), Some(Node::Paragraph(_))) {
+            self.html.push_str(checkbox.take().unwrap_or_default());
+        }
+
+        for child in &item.children {
+            let Node::Paragraph(paragraph) = child else {
+                self.block(child);
+                continue;
+            };
+
+            if !tight {
+                self.line();
+                self.html.push_str("<p>");
+            }
+            self.html.push_str(checkbox.take().unwrap_or_default());
+            self.inlines(&paragraph.children);
+            if !tight {
+                self.html.push_str("</p>");
+            }
+        }
+
+        let inline = match item.children.last() {
+            None => true,
+            Some(Node::Paragraph(_)) => tight,
+            Some(_) => false,
+        };
+        if !inline {
+            self.line();
+        }
+        self.html.push_str("</li>");
+    }
+
+    /// Render a code block, highlighting the code if the language is
+    /// supported.
+    fn code(&mut self, code: &'a Code) {
+        let value = match code.value.as_str() {
+            "" => String::new(),
+            value => format!("{value}\n"),
+        };
+
+        self.line();
+        self.html.push_str("<pre><code");
+        let highlighted = code.lang.as_deref().and_then(|lang| {
+            let _ = write!(self.html, " class=\"language-{}\"", escape(lang));
+            crate::highlight::highlight(&value, lang)
+        });
+        self.html.push('>');
+        match highlighted {
+            Some(highlighted) => self.html.push_str(&highlighted),
+            None => self.html.push_str(&escape(&value)),
+        }
+        self.html.push_str("</code></pre>");
+    }
+
+    fn table(&mut self, table: &'a Table) {
+        self.line();
+        self.html.push_str("<table>");
+
+        let mut rows = table.children.iter();
+        if let Some(head) = rows.next() {
+            self.html.push_str("\n<thead>");
+            self.table_row(head, &table.align, "th");
+            self.html.push_str("\n</thead>");
+        }
+
+        let body = rows.as_slice();
+        if !body.is_empty() {
+            self.html.push_str("\n<tbody>");
+            for row in body {
+                self.table_row(row, &table.align, "td");
+            }
+            self.html.push_str("\n</tbody>");
+        }
+
+        self.html.push_str("\n</table>");
+    }
+
+    /// Render a row of the table, rows are padded or truncated to the same
+    /// number of cells as the table has columns.
+    fn table_row(&mut self, row: &'a Node, align: &[AlignKind], tag: &str) {
+        let cells = row.children().map(Vec::as_slice).unwrap_or_default();
+
+        self.html.push_str("\n<tr>");
+        for (i, align) in align.iter().enumerate() {
+            let align = match align {
+                AlignKind::Left => " align=\"left\"",
+                AlignKind::Right => " align=\"right\"",
+                AlignKind::Center => " align=\"center\"",
+                AlignKind::None => "",
+            };
+            let _ = write!(self.html, "\n<{tag}{align}>");
+            if let Some(cell) = cells.get(i) {
+                self.inlines(cell.children().map_or(&[], Vec::as_slice));
+            }
+            let _ = write!(self.html, "</{tag}>");
+        }
+        self.html.push_str("\n</tr>");
+    }
+
+    fn link(&mut self, url: &str, title: Option<&str>, children: &'a [Node]) {
+        let href = sanitize_with_protocols(url, &SAFE_PROTOCOL_HREF);
+        let _ = write!(self.html, "<a href=\"{href}\"");
+        if let Some(title) = title {
+            let _ = write!(self.html, " title=\"{}\"", escape(title));
+        }
+        // classify the sanitized URL so that removed URLs (i.e. with a
+        // protocol that isn't allowed) aren't treated as external
+        self.html.push_str(LinkKind::of(&href).attributes());
+        self.html.push('>');
+        self.inlines(children);
+        self.html.push_str("</a>");
+    }
+
+    fn image(&mut self, url: &str, title: Option<&str>, alt: &str) {
+        let src = sanitize_with_protocols(url, &SAFE_PROTOCOL_SRC);
+        let _ = write!(self.html, "<img src=\"{src}\" alt=\"{}\"", escape(alt));
+        if let Some(title) = title {
+            let _ = write!(self.html, " title=\"{}\"", escape(title));
+        }
+        self.html.push_str(" />");
+    }
+
+    /// Render a reference to a footnote, these are numbered in the order the
+    /// footnotes are first referenced.
+    fn footnote_reference(&mut self, identifier: &'a str) {
+        let index =
+            match self.calls.iter().position(|(id, _)| *id == identifier) {
+                Some(index) => index,
+                None => {
+                    self.calls.push((identifier, 0));
+                    self.calls.len() - 1
+                }
+            };
+        self.calls[index].1 += 1;
+
+        let id = self.footnote_id(identifier);
+        let number = index + 1;
+        let suffix = match self.calls[index].1 {
+            1 => String::new(),
+            count => format!("-{count}"),
+        };
+        let _ = write!(
+            self.html,
+            "<sup><a href=\"#user-content-fn-{id}\" \
+             id=\"user-content-fnref-{id}{suffix}\" data-footnote-ref=\"\" \
+             aria-describedby=\"{prefix}footnote-label\" \
+             hx-boost=\"false\">{number}</a></sup>",
+            prefix = self.id_prefix,
+        );
+    }
+
+    /// Render every referenced footnote in a section at the end of the HTML.
+    ///
+    /// Each footnote links back to each of its references, the links are
+    /// added to the end of the last paragraph if it ends with one.
+    fn footnote_section(&mut self) {
+        if self.calls.is_empty() {
+            return;
+        }
+
+        // footnotes can reference other footnotes so more may be added, every
+        // footnote is rendered before the back references are added so that
+        // the references within later footnotes are counted
+        let mut rendered = Vec::new();
+        let mut index = 0;
+        while index < self.calls.len() {
+            let identifier = self.calls[index].0;
+            if let Some(definition) = self.footnotes.get(identifier).copied() {
+                // render the definition on its own so that the back
+                // references can be added to the end of it
+                let html = std::mem::take(&mut self.html);
+                self.blocks(&definition.children);
+                let content = std::mem::replace(&mut self.html, html);
+                rendered.push((index, content));
+            }
+            index += 1;
+        }
+
+        self.line();
+        let _ = write!(
+            self.html,
+            "<section data-footnotes=\"\" class=\"footnotes\"><h2 \
+             id=\"{}footnote-label\" class=\"footnotes-label\">Footnotes</h2>\n\
+             <ol>",
+            self.id_prefix,
+        );
+
+        for (index, content) in rendered {
+            let (identifier, count) = self.calls[index];
+            let id = self.footnote_id(identifier);
+            let backreferences = (1..=count)
+                .map(|i| {
+                    let (suffix, sup) = match i {
+                        1 => (String::new(), String::new()),
+                        i => (format!("-{i}"), format!("<sup>{i}</sup>")),
+                    };
+                    format!(
+                        "<a href=\"#user-content-fnref-{id}{suffix}\" \
+                         data-footnote-backref=\"\" \
+                         aria-label=\"Back to content\" \
+                         class=\"data-footnote-backref\" \
+                         hx-boost=\"false\">↩{sup}</a>"
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let _ = write!(self.html, "\n<li id=\"user-content-fn-{id}\">\n");
+            match content.strip_suffix("</p>") {
+                Some(content) => {
+                    let _ = write!(self.html, "{content} {backreferences}</p>");
+                }
+                None => {
+                    self.html.push_str(&content);
+                    self.line();
+                    self.html.push_str(&backreferences);
+                }
+            }
+            self.line();
+            self.html.push_str("</li>");
+        }
+
+        self.html.push_str("\n</ol>\n</section>\n");
+    }
+
+    /// The `id` of a footnote used in the `href` of its references.
+    fn footnote_id(&self, identifier: &str) -> String {
+        let id = markdown::sanitize(&identifier.to_lowercase());
+        format!("{}{id}", self.id_prefix)
+    }
+}
+
+/// The content of inline code as it is rendered.
+///
+/// Line endings are turned into spaces and a single space is stripped from
+/// both ends if there is one on each end, so that code starting or ending
+/// with a backtick can be written (e.g. ``` `` `a` `` ```). Code that is
+/// only spaces is left as is.
+fn inline_code(value: &str) -> String {
+    let value = value.replace("\r\n", " ").replace(['\r', '\n'], " ");
+    match value.strip_prefix(' ').and_then(|v| v.strip_suffix(' ')) {
+        Some(inner) if value.bytes().any(|b| b != b' ') => inner.to_owned(),
+        _ => value,
+    }
+}
+
+/// Escape the text so that it can be used within HTML, including attribute
+/// values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Make the URL safe to use as an attribute value, URLs with a protocol that
+/// isn't allowed are removed (i.e. the empty string).
+///
+/// The protocol must come before any path, query or fragment, otherwise the
+/// URL is relative (e.g. `?a=b:c`).
+fn sanitize_with_protocols(url: &str, protocols: &[&str]) -> String {
+    let url = markdown::sanitize(url);
+
+    let end = url.find(['?', '#', '/']).unwrap_or(url.len());
+    match url[..end].find(':') {
+        Some(colon) if !protocols.contains(&&*url[..colon].to_lowercase()) => {
+            String::new()
+        }
+        _ => url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use markdown::{CompileOptions, Options};
+
+    use super::*;
+
+    /// Parse the Markdown and render it with the anchors of its headings.
+    fn render_markdown(markdown: &str) -> String {
+        let ast =
+            markdown::to_mdast(markdown, &posts::parse_options()).unwrap();
+        render(&ast, &posts::toc(&ast), "")
+    }
+
+    /// Render the Markdown using `markdown` with the same options.
+    fn to_html(markdown: &str) -> String {
+        let options = Options {
+            parse: posts::parse_options(),
+            compile: CompileOptions {
+                gfm_footnote_label_attributes: Some(
+                    "class=\"footnotes-label\"".to_owned(),
+                ),
+                ..CompileOptions::gfm()
+            },
+        };
+        markdown::to_html_with_options(markdown, &options).unwrap()
+    }
+
+    #[test]
+    fn renders_the_same_as_markdown() {
+        let cases = [
+            "Some *emphasis*, **strong**, ~~deleted~~ and `code`.",
+            "`` `a` `` ` b ` `  c ` `   ` ` ``\n` ` x`",
+            "Line one  \nline two\\\nline three &amp; &copy; \\* \\<tag\\>.",
+            "Inline <span>html</span>.\n\n<div>\nblock html\n</div>",
+            "# Heading\n\nSetext\n------",
+            "> Quote\n>\n> > Nested\n\n>",
+            "```\nplain <b>\n```\n\n```\n```\n\n    indented",
+            "1. tight\n2. list\n\n3) start\n4) three",
+            "- loose\n\n- list\n  with lazy\n\n* tight\n  * nested\n    * more",
+            "- one\n- two\n\n  two paragraphs",
+            "- [x] done\n- [ ] todo\n\n* [ ] loose\n\n  more",
+            "- > quote\n- ```\n  code\n  ```\n-\n- text",
+            "| a | `b \\| c` | d |\n| - | :-: | -: |\n| 1 | 2 |\n| 3 | 4 | 5 | 6 |",
+            "| head |\n| :- |",
+            "---\n***",
+            "[a](/a \"title\") [b][ref] [c][] [ref] <https://a.b> <me@a.b>\n\n\
+             [ref]: https://example.com \"Ref\"\n[c]: /c\n[ref]: /ignored",
+            "[x](javascript:alert) [y](a:b/c) [z](?a=b:c) [w](</a b>) \
+             www.example.com [q](https://x.com/?a=1&b=\"2\")",
+            "![An *image*](/a.png \"Title\") ![ref] ![x](data:x)\n\n\
+             [ref]: https://example.com/a.png",
+            "Note[^a] and again[^a] and[^B].\n\n[^a]: First\n\n    \
+             Second.\n\n[^b]: > Quote\n\n[^unused]: Unused.",
+            "Text[^a] and[^b].\n\n[^a]: A[^b].\n\n[^b]: B[^a][^c].\n\n\
+             [^c]: C[^a].",
+        ];
+
+        for markdown in cases {
+            let ast =
+                markdown::to_mdast(markdown, &posts::parse_options()).unwrap();
+            // `markdown` doesn't add the attributes for the kind of link
+            let html = [LinkKind::External, LinkKind::Anchor]
+                .into_iter()
+                .fold(render(&ast, &[], ""), |html, kind| {
+                    html.replace(kind.attributes(), "")
+                });
+            assert_eq!(
+                html.trim_end(),
+                to_html(markdown).trim_end(),
+                "{markdown:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn headings_have_anchors() {
+        let html = render_markdown("## Usage\n\n### `code` & *more*");
+        assert_eq!(
+            html,
+            "<h2 id=\"usage\">Usage<a class=\"anchor\" href=\"#usage\" \
+             hx-boost=\"false\" aria-label=\"Permalink\">#</a></h2>\n<h3 \
+             id=\"code-and-more\"><code>code</code> &amp; <em>more</em><a \
+             class=\"anchor\" href=\"#code-and-more\" hx-boost=\"false\" \
+             aria-label=\"Permalink\">#</a></h3>"
+        );
+    }
+
+    #[test]
+    fn anchors_match_their_heading() {
+        // raw HTML headings aren't in the AST and footnotes are rendered at
+        // the end, neither should shift the anchors of the other headings
+        let html = render_markdown(
+            "<h2>Raw</h2>\n\n## Usage[^1]\n\n\
+             [^1]: ## In a footnote\n\n## Usage\n\n##\n\n## Setup",
+        );
+
+        let ids = html
+            .split(" id=\"")
+            .skip(1)
+            .filter_map(|s| s.split_once('"').map(|(id, _)| id))
+            // skip the footnote references and the footnotes label
+            .filter(|id| !id.contains("fn") && *id != "footnote-label")
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["usage", "usage-1", "section", "setup"]);
+        assert!(html.contains("<h2 id=\"usage-1\">Usage<a"));
+        assert!(html.contains("<h2 id=\"setup\">Setup<a"));
+        // headings within footnotes aren't in the table of contents
+        assert!(html.contains("<h2>In a footnote</h2>"));
+    }
+
+    #[test]
+    fn footnote_ids_are_prefixed() {
+        let ast = markdown::to_mdast(
+            "Note[^1].\n\n[^1]: Text.",
+            &posts::parse_options(),
+        )
+        .unwrap();
+        let html = render(&ast, &[], "post-");
+        assert_eq!(
+            html,
+            "<p>Note<sup><a href=\"#user-content-fn-post-1\" \
+             id=\"user-content-fnref-post-1\" data-footnote-ref=\"\" \
+             aria-describedby=\"post-footnote-label\" \
+             hx-boost=\"false\">1</a></sup>.</p>\n<section \
+             data-footnotes=\"\" class=\"footnotes\"><h2 \
+             id=\"post-footnote-label\" \
+             class=\"footnotes-label\">Footnotes</h2>\n<ol>\n<li \
+             id=\"user-content-fn-post-1\">\n<p>Text. <a \
+             href=\"#user-content-fnref-post-1\" data-footnote-backref=\"\" \
+             aria-label=\"Back to content\" \
+             class=\"data-footnote-backref\" \
+             hx-boost=\"false\">↩</a></p>\n</li>\n</ol>\n</section>\n"
+        );
+    }
+
+    #[test]
+    fn unknown_languages_are_plain_text() {
+        let html = render_markdown("```not-a-language\n<b>&</b>\n```");
+        assert_eq!(
+            html,
+            "<pre><code class=\"language-not-a-language\">&lt;b&gt;&amp;\
+             &lt;/b&gt;\n</code></pre>"
+        );
+    }
+
+    #[test]
+    fn link_kinds() {
+        let cases = [
+            ("https://github.com", LinkKind::External),
+            ("HTTP://example.com", LinkKind::External),
+            ("mailto:me@example.com", LinkKind::External),
+            ("//example.com/path", LinkKind::External),
+            // any scheme is external, `sanitize_with_protocols` removes the
+            // unsafe ones
+            ("a:b/c", LinkKind::External),
+            ("/projects", LinkKind::Internal),
+            ("projects/home-lab", LinkKind::Internal),
+            ("../blog", LinkKind::Internal),
+            ("?q=a:b", LinkKind::Internal),
+            ("/search?q=a:b", LinkKind::Internal),
+            ("", LinkKind::Internal),
+            ("#usage", LinkKind::Anchor),
+            ("#a:b", LinkKind::Anchor),
+        ];
+
+        for (url, kind) in cases {
+            assert_eq!(LinkKind::of(url), kind, "{url:?}");
+        }
+    }
+
+    #[test]
+    fn links_have_attributes_for_their_kind() {
+        let html = render_markdown(
+            "[a](https://a.com) [b](/b) [c](#c) [d][d] <me@a.com> \
+             [e](javascript:alert(1))\n\n[d]: //d.com",
+        );
+        assert_eq!(
+            html,
+            "<p><a href=\"https://a.com\" target=\"_blank\" \
+             rel=\"noopener noreferrer\">a</a> <a href=\"/b\">b</a> <a \
+             href=\"#c\" hx-boost=\"false\">c</a> <a href=\"//d.com\" \
+             target=\"_blank\" rel=\"noopener noreferrer\">d</a> <a \
+             href=\"mailto:me@a.com\" target=\"_blank\" \
+             rel=\"noopener noreferrer\">me@a.com</a> <a href=\"\">e</a></p>"
+        );
+    }
+}
@@ -0,0 +1,46 @@
+//! Partial rendering for htmx requests.
+//!
+//! Every link is boosted (`hx-boost="true"` in `base.html`) so htmx requests
+//! the next page and swaps it into `<main>`. These requests only need the
+//! `content` block and the `<title>` of the page rather than the entire page.
+//!
+//! The `scope` middleware records whether the current request is an htmx
+//! request so that `base.html` can check it using `partial()` without every
+//! page needing an extra field.
+
+use axum::{
+    http::{HeaderMap, HeaderName, Request},
+    middleware::Next,
+    response::Response,
+};
+
+/// Set by htmx on every request it makes.
+pub const HX_REQUEST: HeaderName = HeaderName::from_static("hx-request");
+
+/// Set by htmx when it requests a page to restore the history (e.g. after a
+/// refresh), in which case it needs the entire page.
+pub const HX_HISTORY_RESTORE_REQUEST: HeaderName =
+    HeaderName::from_static("hx-history-restore-request");
+
+tokio::task_local! {
+    /// Whether only the content of the page should be rendered.
+    static PARTIAL: bool;
+}
+
+/// Check if only the content of the page should be rendered.
+///
+/// This is always `false` outside of a request (e.g. during an export).
+pub fn partial() -> bool {
+    PARTIAL.try_with(|partial| *partial).unwrap_or(false)
+}
+
+/// Record whether the request is an htmx request while it is being handled.
+pub async fn scope<B>(request: Request<B>, next: Next<B>) -> Response {
+    let partial = is_partial(request.headers());
+    PARTIAL.scope(partial, next.run(request)).await
+}
+
+fn is_partial(headers: &HeaderMap) -> bool {
+    let is_true = |name| headers.get(name).is_some_and(|v| v == "true");
+    is_true(HX_REQUEST) && !is_true(HX_HISTORY_RESTORE_REQUEST)
+}
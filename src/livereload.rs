@@ -0,0 +1,54 @@
+//! Live reload for development.
+//!
+//! Browsers connect to the `/dev/livereload` endpoint using Server-Sent Events
+//! (SSE). Whenever the content is reloaded a `reload` event is sent to every
+//! connected browser which then refreshes the page.
+//!
+//! Live reload is only available in development mode, the router is only
+//! nested and the script only injected by `base.html` when
+//! `Config::is_development` is true.
+//!
+//! Templates are compiled into the binary so a template change requires the
+//! server to be restarted (e.g. with `cargo watch -x run`). The browser will
+//! lose its connection while the server restarts and will refresh the page
+//! once it has reconnected.
+
+use std::convert::Infallible;
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+    Router,
+};
+use futures_util::{stream, Stream};
+
+use crate::content::ContentStore;
+use crate::AppState;
+
+/// The URL that the live reload router should be nested at.
+pub const PATH: &str = "/dev/livereload";
+
+/// Send a `reload` event every time the content changes.
+#[instrument(skip_all)]
+async fn events(
+    State(store): State<ContentStore>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    debug!("browser connected to live reload");
+
+    let stream = stream::unfold(store.subscribe(), |mut rx| async move {
+        // the sender is never dropped while the server is running, if it is
+        // then the stream ends and the browser will try to reconnect
+        rx.changed().await.ok()?;
+
+        trace!("sending reload event");
+        let event = Event::default().event("reload").data("content changed");
+        Some((Ok(event), rx))
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", get(events))
+}
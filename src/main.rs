@@ -1,13 +1,18 @@
-#![feature(async_closure)]
+use std::sync::Arc;
 
-use anyhow::Context;
-use askama::Template;
+use anyhow::{bail, Context};
 use axum::{
-    http::header, middleware::map_response, response::Response, Router,
+    extract::{FromRef, State},
+    http::header,
+    middleware::{from_fn, from_fn_with_state, map_response_with_state},
+    response::Response,
+    Router,
 };
-use tower::ServiceBuilder;
 use tower_http::{
-    compression::CompressionLayer,
+    compression::{
+        predicate::{DefaultPredicate, NotForContentType, Predicate},
+        CompressionLayer,
+    },
     services::{ServeDir, ServeFile},
     trace::TraceLayer,
 };
@@ -19,13 +24,29 @@ extern crate tracing;
 
 pub use error::AppError;
 
+mod alerts;
+mod blog;
 pub mod caching;
+pub mod config;
+pub mod content;
+pub mod dates;
 pub mod error;
+pub mod export;
+pub mod feeds;
+mod filters;
+mod highlight;
 mod home;
+mod html;
+pub mod htmx;
 pub mod links;
+pub mod livereload;
 pub mod posts;
 mod projects;
 mod resume;
+mod search;
+mod sitemap;
+mod tags;
+pub mod watcher;
 mod wishlist;
 
 #[tokio::main]
@@ -40,72 +61,206 @@ async fn main() -> anyhow::Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    // the first argument is the command to run (default: `serve`)
+    let mut args = std::env::args().skip(1);
+    let command = args.next();
+
+    let mut config =
+        config::Config::load().context("failed to load configuration")?;
+    // the exported site is served without this server so it can't include
+    // any development only features (e.g. live reload)
+    if command.as_deref() == Some("export") {
+        config.mode = config::Mode::Production;
+    }
+    let config = Arc::new(config);
+
+    info!("loading content...");
+
+    // this will fail if any of the posts fail to parse
+    let state = AppState {
+        content: content::ContentStore::load(config.clone())
+            .context("failed to load content")?,
+        config,
+    };
+
+    match command.as_deref() {
+        None | Some("serve") => serve(state).await,
+        Some("export") => {
+            let out = args
+                .next()
+                .unwrap_or_else(|| export::DEFAULT_OUT_DIR.to_owned());
+            let content = state.content.get();
+            let config = state.config.clone();
+
+            info!("exporting site to {}...", out);
+            export::export(router(state)?, &config, &content, out.as_ref())
+                .await?;
+            info!("site exported to {}", out);
+
+            Ok(())
+        }
+        Some(command) => bail!("unknown command: {command}"),
+    }
+}
+
+/// Run the web server.
+async fn serve(state: AppState) -> anyhow::Result<()> {
+    // reload the content whenever any of the posts change, this must be kept
+    // alive until the server stops
+    let _watcher = watcher::watch(state.content.clone())?;
+
+    let addr = state.config.addr();
+    let router = router(state)?;
+
+    info!("router initialized, now listening on {}", addr);
+
+    axum::Server::bind(&addr)
+        .serve(router.into_make_service())
+        .await
+        .context("error while starting server")?;
+
+    Ok(())
+}
+
+/// Create the router for the entire site.
+fn router(state: AppState) -> anyhow::Result<Router> {
     info!("initializing router...");
 
-    // get host and port from environment variables
-    // default: 127.0.0.1:3000
-    let host: std::net::IpAddr = std::option_env!("HOST")
-        .map(|h| h.parse().expect("could not parse HOST"))
-        .unwrap_or([127, 0, 0, 1].into());
-    let port = std::option_env!("PORT")
-        .map(|p| p.parse().expect("could not parse PORT"))
-        .unwrap_or(3000);
-    let addr = std::net::SocketAddr::from((host, port));
-
-    let router = Router::new()
+    let public_dir = std::env::current_dir()?.join(&state.config.public_dir);
+    let cache = cache_layer(&state.config);
+
+    // store the rendered pages until the content changes
+    let response_cache = caching::ResponseCache::new(Default::default());
+    response_cache.clear_on_change(state.content.subscribe());
+
+    let mut router = Router::new()
         .merge(home::router())
+        .merge(sitemap::router())
         .nest("/resume", resume::router())
         .nest("/projects", projects::router())
         .nest("/wishlist", wishlist::router())
-        // serve all files from `./public` directory
-        .nest_service(
-            "/public",
-            ServeDir::new(std::env::current_dir()?.join("public")),
-        )
+        .nest("/blog", blog::router())
+        .nest("/search", search::router())
+        .nest("/tags", tags::router())
+        // serve all files from the public directory (default: `./public`)
+        .nest_service("/public", ServeDir::new(&public_dir))
         // serve the favicon separately since browsers expect it to be located
         // at a specific URL
-        .route_service("/favicon.ico", ServeFile::new("public/favicon.ico"))
-        .fallback(async || NotFoundPage)
+        .route_service(
+            "/favicon.ico",
+            ServeFile::new(public_dir.join("favicon.ico")),
+        );
+
+    // only allow browsers to live reload during development
+    if state.config.is_development() {
+        info!("live reload enabled at {}", livereload::PATH);
+        router = router.nest(livereload::PATH, livereload::router());
+    }
+
+    let router = router
+        .fallback(not_found)
+        // render a page for any errors returned from the handlers
+        .layer(map_response_with_state(
+            state.config.clone(),
+            error::render_error_page,
+        ))
+        // every page is rendered from the content so it was last modified
+        // when the content was loaded
+        .layer(from_fn_with_state(
+            state.content.clone(),
+            content::set_last_modified,
+        ))
+        // only render the content of the page for htmx requests
+        .layer(from_fn(htmx::scope))
+        .with_state(state)
         .layer(
-            // add tracing and compression to all routes
-            ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CompressionLayer::new()),
+            CompressionLayer::new().compress_when(
+                // don't compress server-sent events since the encoder would
+                // buffer the events instead of sending them
+                DefaultPredicate::new()
+                    .and(NotForContentType::const_new(caching::EVENT_STREAM)),
+            ),
         )
-        // set the vary header to (at-least) accept-encoding
-        .layer(map_response(set_vary_header))
-        .layer(caching::CacheLayer::default());
+        // the vary header must include (at-least) accept-encoding since all
+        // the resources are being compressed
+        // Source: https://developer.mozilla.org/en-US/docs/Web/HTTP/Compression#end-to-end_compression
+        // and the htmx headers since pages are rendered differently for htmx
+        // requests
+        .layer(caching::VaryLayer::new([
+            header::ACCEPT_ENCODING,
+            htmx::HX_REQUEST,
+            htmx::HX_HISTORY_RESTORE_REQUEST,
+        ]))
+        // the files are served from disk so they are only stored by the
+        // browser, otherwise a changed file wouldn't be sent until the
+        // content is reloaded
+        .layer(
+            caching::ResponseCacheLayer::new(response_cache)
+                .skip("/public/*")
+                .skip("/favicon.ico"),
+        )
+        // set the cache policy for each route
+        .layer(cache)
+        // trace every request (including the responses sent by the caches)
+        .layer(TraceLayer::new_for_http());
 
-    info!("router initialized, now listening on port {}", port);
+    Ok(router)
+}
 
-    axum::Server::bind(&addr)
-        .serve(router.into_make_service())
-        .await
-        .context("error while starting server")?;
+/// Create the cache policies for every route.
+///
+/// Pages are revalidated using an `ETag` and static files are cached for a
+/// short time. Error responses are never cached.
+fn cache_layer(config: &config::Config) -> caching::CacheLayer {
+    use caching::{CacheControl, CacheLayer, Options};
 
-    Ok(())
+    const HOUR: u32 = 60 * 60;
+    const DAY: u32 = HOUR * 24;
+
+    // always revalidate in development so that changes show up immediately
+    if config.is_development() {
+        return CacheLayer::new(Options {
+            cache_control: CacheControl::new().public().no_cache().max_age(0),
+            etag: true,
+        });
+    }
+
+    let pages = Options {
+        cache_control: CacheControl::new()
+            .public()
+            .max_age(60)
+            .must_revalidate(),
+        etag: true,
+    };
+
+    // the files in the public directory aren't fingerprinted (i.e. a changed
+    // file keeps the same URL) so they can only be cached for a short time
+    // before being revalidated
+    let assets = Options {
+        cache_control: CacheControl::new().public().max_age(HOUR),
+        etag: true,
+    };
+
+    // the favicon rarely changes and must be at a fixed URL
+    let favicon = Options {
+        cache_control: CacheControl::new().public().max_age(DAY),
+        etag: true,
+    };
+
+    CacheLayer::new(pages)
+        .policy("/public/*", assets)
+        .policy("/favicon.ico", favicon)
 }
 
-/// Set the `Vary` header to include (at-least) `Accept-Encoding` since all the
-/// resources are being compressed.
-///
-/// Recommendation by MDN:
-/// "the server must send a Vary header containing at least Accept-Encoding
-/// alongside this header in the response."
-///
-/// Source:
-/// - https://developer.mozilla.org/en-US/docs/Web/HTTP/Compression#end-to-end_compression
-///
-/// **NOTE:** this is being added to the `CompressionLayer` in a PR:
-/// - https://github.com/tower-rs/tower-http/pull/399
-async fn set_vary_header(mut response: Response) -> Response {
-    // TODO: support appending to the end of the existing Vary header
-    response
-        .headers_mut()
-        .insert(header::VARY, header::ACCEPT_ENCODING.into());
-    response
+/// State shared between all of the routers.
+#[derive(Debug, Clone, FromRef)]
+pub struct AppState {
+    /// The configuration loaded at startup.
+    pub config: Arc<config::Config>,
+    /// All of the content, this is reloaded when the posts change.
+    pub content: content::ContentStore,
 }
 
-#[derive(Template)]
-#[template(path = "pages/404.html")]
-struct NotFoundPage;
+async fn not_found(State(config): State<Arc<config::Config>>) -> Response {
+    error::not_found_page(&config)
+}
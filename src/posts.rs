@@ -1,13 +1,15 @@
 //! Generic post type.
 //!
 //! This can then be used to generate different types of posts (e.g. projects,
-//! blog posts, etc.).
+//! blog posts, etc.). Each type of post is a `Section` which loads every post
+//! within a directory.
 
+use std::collections::HashSet;
 use std::fmt::{Debug, Display};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use anyhow::bail;
-use markdown::{Constructs, Options, ParseOptions};
+use anyhow::{bail, Context};
+use markdown::{Constructs, ParseOptions};
 
 /// A post with some generic frontmatter and parsed markdown content.
 pub struct Post<Frontmatter, Metadata> {
@@ -20,6 +22,14 @@ pub struct Post<Frontmatter, Metadata> {
     /// parsing step.
     pub content: Content,
 
+    /// The plain text of the post (i.e. without any markdown syntax or
+    /// frontmatter) which is used for searching.
+    pub text: String,
+
+    /// The headings within the content which are used to create a table of
+    /// contents.
+    pub toc: Vec<Heading>,
+
     /// Additional metadata that can be used to pass to the template.
     ///
     /// This is not an `Option` since if you wanted to not use metadata you can
@@ -28,13 +38,56 @@ pub struct Post<Frontmatter, Metadata> {
     pub metadata: Metadata,
 }
 
+/// A heading within the content of a post.
+#[derive(Debug, Clone)]
+pub struct Heading {
+    /// The level of the heading (i.e. `1` for `<h1>`).
+    pub level: u8,
+    /// The plain text of the heading.
+    pub text: String,
+    /// The `id` of the heading, this is unique within the post.
+    pub anchor: String,
+    /// The byte offset of the heading within the Markdown, this is used to
+    /// find the heading when rendering the HTML.
+    pub(crate) offset: usize,
+}
+
+/// Metadata about a post within a `Section`.
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    /// The URL of the post (e.g. `/projects/personal-site`).
+    pub slug: String,
+    /// Hidden posts (file name starts with `_`) are not listed but can still
+    /// be accessed directly. Hidden posts are never included in the feeds,
+    /// sitemap or static export.
+    pub hidden: bool,
+}
+
+/// A directory of posts with the same type of frontmatter (e.g. projects).
+///
+/// The slug of each post is the URL prefix followed by the file stem (e.g.
+/// `/projects` and `personal-site.md` is `/projects/personal-site`).
+pub struct Section<Frontmatter, Key> {
+    /// The name of the section used when logging (e.g. `projects`).
+    name: &'static str,
+    dir: PathBuf,
+    prefix: &'static str,
+    /// The posts are sorted by this key, largest first.
+    sort_key: fn(&Frontmatter) -> Key,
+    /// Whether hidden posts are loaded.
+    include_hidden: bool,
+    /// Whether the `id`s within each post are prefixed with its slug.
+    prefix_ids: bool,
+}
+
 /// The content of a post.
 ///
-/// Posts are written in markdown and therefore are initially parsed as
-/// `Markdown`. To parse the content into HTML use the `parse_content` method.
+/// Posts are written in markdown and therefore are initially parsed as a
+/// `Markdown` AST. To render the content into HTML use the `parse_content`
+/// method, this uses the same AST so each post is only parsed once.
 #[derive(Debug, Clone)]
 pub enum Content {
-    Markdown(String),
+    Markdown(markdown::mdast::Node),
     Html(String),
 }
 
@@ -58,9 +111,10 @@ impl Content {
 
 impl Display for Content {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        // Use the underlying `String` to display the content.
+        // Use the plain text of the AST or the underlying `String` to display
+        // the content.
         match self {
-            Content::Markdown(content) => write!(f, "{}", content),
+            Content::Markdown(ast) => write!(f, "{}", ast.to_string()),
             Content::Html(content) => write!(f, "{}", content),
         }
     }
@@ -87,26 +141,46 @@ where
         path: &Path,
         metadata: Metadata,
     ) -> anyhow::Result<Self> {
-        let file = std::fs::read_to_string(path)?;
+        let file = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read {path:?}"))?;
         trace!("open file {:?}", path);
 
-        let frontmatter = parse_frontmatter(&file)?;
+        // parse the file into a Markdown AST
+        let Ok(ast) = markdown::to_mdast(&file, &parse_options()) else {
+            bail!("invalid markdown file {path:?}");
+        };
+
+        let frontmatter = parse_frontmatter(&ast)
+            .with_context(|| format!("invalid frontmatter in {path:?}"))?;
         trace!("parse frontmatter");
 
+        let mut text = String::new();
+        plain_text(&ast, &mut text);
+
         Ok(Post {
             frontmatter,
-            content: Content::Markdown(file),
+            text: text.trim().to_owned(),
+            toc: toc(&ast),
+            content: Content::Markdown(ast),
             metadata,
         })
     }
 
     /// Parse the Markdown content within the post.
     ///
-    /// This will replace the `Markdown` variant with `Html` variant.
-    pub fn parse_content(&mut self) -> anyhow::Result<()> {
+    /// This will replace the `Markdown` variant with `Html` variant. Each
+    /// heading is given its anchor as an `id` along with a permalink.
+    ///
+    /// Every `id` (including the anchors within the table of contents) starts
+    /// with `id_prefix`, this keeps the `id`s unique when several posts are
+    /// rendered on the same page.
+    pub fn parse_content(&mut self, id_prefix: &str) -> anyhow::Result<()> {
         match self.content {
-            Content::Markdown(ref file) => {
-                let content = parse_content(file)?;
+            Content::Markdown(ref ast) => {
+                for heading in &mut self.toc {
+                    heading.anchor.insert_str(0, id_prefix);
+                }
+                let content = crate::html::render(ast, &self.toc, id_prefix);
                 self.content = Content::Html(content);
                 Ok(())
             }
@@ -117,27 +191,173 @@ where
     }
 }
 
-/// Parse the frontmatter from the given file.
+impl<Frontmatter, Key> Section<Frontmatter, Key>
+where
+    Frontmatter: serde::de::DeserializeOwned,
+    Key: Ord,
+{
+    /// Create a new section of posts within `dir`.
+    ///
+    /// Hidden posts are loaded by default.
+    pub fn new(
+        name: &'static str,
+        dir: impl Into<PathBuf>,
+        prefix: &'static str,
+        sort_key: fn(&Frontmatter) -> Key,
+    ) -> Self {
+        Self {
+            name,
+            dir: dir.into(),
+            prefix,
+            sort_key,
+            include_hidden: true,
+            prefix_ids: false,
+        }
+    }
+
+    /// Set whether hidden posts are loaded.
+    pub fn include_hidden(mut self, include_hidden: bool) -> Self {
+        self.include_hidden = include_hidden;
+        self
+    }
+
+    /// Set whether the `id`s within each post (e.g. the anchors of headings)
+    /// are prefixed with the slug of the file stem (e.g. `home-lab-usage`).
+    ///
+    /// This is needed when every post is rendered on the same page, otherwise
+    /// posts with the same heading or footnote would have the same `id`.
+    pub fn prefix_ids(mut self, prefix_ids: bool) -> Self {
+        self.prefix_ids = prefix_ids;
+        self
+    }
+
+    /// Load every post within the directory and parse the content into HTML.
+    #[instrument(skip_all, fields(section = self.name))]
+    pub fn load(&self) -> anyhow::Result<Vec<Post<Frontmatter, Metadata>>> {
+        let dir = &self.dir;
+
+        // search the directory for markdown files
+        let paths = std::fs::read_dir(dir)
+            .with_context(|| format!("could not read the {dir:?} directory"))?
+            .filter_map(|res| {
+                // filter out and log errors
+                if let Err(e) = res {
+                    warn!("could not read file: {}", e);
+                    return None;
+                }
+
+                res.ok()
+            })
+            .map(|res| res.path())
+            .filter(|path| {
+                let is_post = path.is_file() && is_post(path);
+                if !is_post {
+                    trace!("skip {path:?}");
+                }
+                is_post
+            })
+            .collect::<Vec<_>>();
+
+        debug!("found {} {} files", paths.len(), self.name);
+
+        // read each file and parse into `Post`
+        let mut posts = paths
+            .iter()
+            .map(|path| {
+                let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+                else {
+                    bail!("could not get file stem");
+                };
+                Ok((path, stem))
+            })
+            .filter_map(|res| res.ok())
+            .map(|(path, stem)| {
+                // files that start with `_` are hidden
+                (path, stem, stem.starts_with('_'))
+            })
+            .filter(|(_, _, hidden)| self.include_hidden || !hidden)
+            .map(|(path, stem, hidden)| {
+                let metadata = Metadata {
+                    slug: format!("{}/{stem}", self.prefix),
+                    hidden,
+                };
+                let mut post = Post::from_file_with_metadata(path, metadata)
+                    .with_context(|| {
+                        format!("failed to create post {path:?}")
+                    })?;
+
+                // actually parse the content into HTML
+                let id_prefix = if self.prefix_ids {
+                    format!("{}-", slug(stem))
+                } else {
+                    String::new()
+                };
+                post.parse_content(&id_prefix).with_context(|| {
+                    format!("failed to parse post {path:?}")
+                })?;
+
+                Ok(post)
+            })
+            // collect into a Vec<_> and propagate Result errors
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        debug!("{} {} parsed", posts.len(), self.name);
+
+        // sort by the largest key first (e.g. the most recent date)
+        posts.sort_by(|a, b| {
+            (self.sort_key)(&b.frontmatter)
+                .cmp(&(self.sort_key)(&a.frontmatter))
+        });
+
+        // make sure the content is HTML. this is a bit redundant since we just
+        // parsed the content into HTML, however, this check should remain so
+        // that the invariant doesn't get lost
+        assert!(
+            posts.iter().all(|p| p.content.is_html()),
+            "all {} files must be parsed into HTML before render",
+            self.name
+        );
+
+        Ok(posts)
+    }
+}
+
+/// Check if the path is a post, i.e. a Markdown file that isn't hidden by the
+/// file system (e.g. `.home-lab.md.swp`).
+///
+/// Anything else within a section's directory (e.g. backups created by an
+/// editor such as `home-lab.md~`) is skipped. Only the name is checked so
+/// this is also true for a post that has been removed.
+pub(crate) fn is_post(path: &Path) -> bool {
+    let is_visible = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| !name.starts_with('.'));
+    let is_markdown = path.extension().is_some_and(|ext| ext == "md");
+
+    is_markdown && is_visible
+}
+
+/// Parse the frontmatter from the given Markdown AST.
 ///
 /// The frontmatter is the first `Yaml` node within a `Root` node.
-#[instrument(skip(file))]
-fn parse_frontmatter<Frontmatter>(file: &str) -> anyhow::Result<Frontmatter>
+#[instrument(skip(ast))]
+fn parse_frontmatter<Frontmatter>(
+    ast: &markdown::mdast::Node,
+) -> anyhow::Result<Frontmatter>
 where
     Frontmatter: serde::de::DeserializeOwned,
 {
     use markdown::mdast::*;
 
-    // parse the file into a Markdown AST
-    let ast = markdown::to_mdast(file, &parse_options());
-
     match ast {
         // the first node in the markdown AST *should* be a `Root`.
         // the first node within the `Root` node is the `Yaml` frontmatter.
-        Ok(Node::Root(Root { children, .. })) => {
+        Node::Root(Root { children, .. }) => {
             // extract the `Yaml` node from the AST
             // if the first node is not `Yaml` then the markdown file doesn't
             // have frontmatter
-            let Some(Node::Yaml(Yaml { value, .. })) = children.get(0) else {
+            let Some(Node::Yaml(Yaml { value, .. })) = children.first() else {
                 bail!("frontmatter not found");
             };
 
@@ -152,40 +372,132 @@ where
     }
 }
 
-/// Parse the given file into HTML.
+/// Collect the text within the Markdown AST (excluding the frontmatter).
 ///
-/// This is assuming that the file is markdown.
-fn parse_content(file: &str) -> anyhow::Result<String> {
-    let Ok(content) = markdown::to_html_with_options(file, &full_options())
-    else {
-        bail!("could not parse markdown file");
+/// Block nodes (e.g. paragraphs) are separated by a new line so that words
+/// from different blocks aren't joined together.
+fn plain_text(node: &markdown::mdast::Node, text: &mut String) {
+    use markdown::mdast::*;
+
+    match node {
+        Node::Yaml(_) | Node::Html(_) => {}
+        Node::Text(Text { value, .. })
+        | Node::InlineCode(InlineCode { value, .. })
+        | Node::Code(Code { value, .. }) => text.push_str(value),
+        node => {
+            for child in node.children().into_iter().flatten() {
+                plain_text(child, text);
+            }
+        }
+    }
+
+    if matches!(
+        node,
+        Node::Paragraph(_)
+            | Node::Heading(_)
+            | Node::Code(_)
+            | Node::ListItem(_)
+            | Node::TableCell(_)
+            | Node::Break(_)
+    ) {
+        text.push('\n');
+    }
+}
+
+/// Collect the headings within the Markdown AST.
+///
+/// The anchor of each heading is the slug of its text, a number is appended
+/// to repeated anchors (e.g. `usage`, `usage-1`, `usage-2`). Headings within
+/// footnotes are skipped since they are rendered at the end of the post
+/// rather than where they are written.
+pub(crate) fn toc(ast: &markdown::mdast::Node) -> Vec<Heading> {
+    let mut toc = Vec::new();
+    headings(ast, &mut toc, &mut HashSet::new());
+    toc
+}
+
+fn headings(
+    node: &markdown::mdast::Node,
+    toc: &mut Vec<Heading>,
+    anchors: &mut HashSet<String>,
+) {
+    use markdown::mdast::Node;
+
+    if let Node::FootnoteDefinition(_) = node {
+        return;
+    }
+
+    let Node::Heading(heading) = node else {
+        for child in node.children().into_iter().flatten() {
+            headings(child, toc, anchors);
+        }
+        return;
     };
 
-    trace!("parse markdown file into HTML successfully");
+    let mut text = String::new();
+    plain_text(node, &mut text);
+    let text = text.trim().to_owned();
 
-    // ensure that all links open in a new tab and don't use HTMX
-    let content = content
-        .replace("<a href=", "<a hx-boost=\"false\" target=\"_blank\" href=");
+    let base = match slug(&text) {
+        slug if slug.is_empty() => "section".to_owned(),
+        slug => slug,
+    };
+    let mut anchor = base.clone();
+    for i in 1.. {
+        if anchors.insert(anchor.clone()) {
+            break;
+        }
+        anchor = format!("{base}-{i}");
+    }
 
-    Ok(content)
+    toc.push(Heading {
+        level: heading.depth,
+        text,
+        anchor,
+        offset: node.position().map_or(0, |p| p.start.offset),
+    });
 }
 
-// use default options, except enable frontmatter parsing
-fn parse_options() -> ParseOptions {
+/// Symbols which are spelled out in slugs so that e.g. `C++`, `C#` and `C`
+/// don't have the same slug.
+const SLUG_SYMBOLS: [(char, &str); 3] =
+    [('+', "plus"), ('#', "sharp"), ('&', "and")];
+
+/// Create a URL safe slug of the text (e.g. `Home Lab` -> `home-lab` and
+/// `C++` -> `c-plus-plus`).
+pub fn slug(text: &str) -> String {
+    let mut words = Vec::new();
+    let mut word = String::new();
+
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            word.extend(c.to_lowercase());
+            continue;
+        }
+
+        if !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        if let Some((_, name)) = SLUG_SYMBOLS.iter().find(|(s, _)| *s == c) {
+            words.push((*name).to_owned());
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words.join("-")
+}
+
+// use GitHub flavored markdown (tables, footnotes, task lists, etc.) and enable
+// frontmatter parsing
+pub(crate) fn parse_options() -> ParseOptions {
     ParseOptions {
         constructs: Constructs {
             frontmatter: true,
-            ..Constructs::default()
+            ..Constructs::gfm()
         },
-        ..ParseOptions::default()
-    }
-}
-
-// use `parse_options()` for parse options and everything else is default
-fn full_options() -> Options {
-    Options {
-        parse: ParseOptions { ..parse_options() },
-        ..Options::default()
+        ..ParseOptions::gfm()
     }
 }
 
@@ -196,6 +508,8 @@ impl<F: Debug, M: Debug> Debug for Post<F, M> {
         f.debug_struct("Post")
             .field("frontmatter", &self.frontmatter)
             .field("content", &self.content)
+            .field("text", &self.text)
+            .field("toc", &self.toc)
             .field("metadata", &self.metadata)
             .finish()
     }
@@ -208,7 +522,116 @@ impl<F: Clone, M: Clone> Clone for Post<F, M> {
         Post {
             frontmatter: self.frontmatter.clone(),
             content: self.content.clone(),
+            text: self.text.clone(),
+            toc: self.toc.clone(),
             metadata: self.metadata.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse the Markdown and collect the level and anchor of each heading.
+    fn anchors(markdown: &str) -> Vec<(u8, String)> {
+        let ast = markdown::to_mdast(markdown, &parse_options()).unwrap();
+        toc(&ast)
+            .into_iter()
+            .map(|heading| (heading.level, heading.anchor))
+            .collect()
+    }
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slug("Home Lab"), "home-lab");
+        assert_eq!(slug("  Hello,   World!  "), "hello-world");
+        assert_eq!(slug("home-lab"), "home-lab");
+        assert_eq!(slug("Café Déjà Vu"), "café-déjà-vu");
+        assert_eq!(slug("ÀÉ 2023"), "àé-2023");
+        assert_eq!(slug("?!"), "");
+        assert_eq!(slug(""), "");
+    }
+
+    #[test]
+    fn slugs_spell_out_symbols() {
+        assert_eq!(slug("C"), "c");
+        assert_eq!(slug("C++"), "c-plus-plus");
+        assert_eq!(slug("C#"), "c-sharp");
+        assert_eq!(slug("F# & .NET"), "f-sharp-and-net");
+        assert_eq!(slug("R&D"), "r-and-d");
+    }
+
+    #[test]
+    fn repeated_anchors_are_numbered() {
+        let anchors = anchors(
+            "# Usage\n\n## Usage\n\n### usage\n\n## Usage 1\n\n## Usage",
+        );
+        assert_eq!(
+            anchors,
+            [
+                (1, "usage".to_owned()),
+                (2, "usage-1".to_owned()),
+                (3, "usage-2".to_owned()),
+                // the slug of the text is the same as a numbered anchor
+                (2, "usage-1-1".to_owned()),
+                (2, "usage-3".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_headings_have_an_anchor() {
+        let anchors = anchors("##\n\n## ?!\n\n## `code` and *em*");
+        assert_eq!(
+            anchors,
+            [
+                (2, "section".to_owned()),
+                (2, "section-1".to_owned()),
+                (2, "code-and-em".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_markdown_files_are_loaded() {
+        #[derive(serde::Deserialize)]
+        struct Frontmatter {
+            name: String,
+        }
+
+        let dir = std::env::temp_dir()
+            .join(format!("personal-site-posts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested.md")).unwrap();
+        let post = "---\nname: Home Lab\n---\n\nText.";
+        for file in ["home-lab.md", "_hidden.md", "notes.txt"] {
+            std::fs::write(dir.join(file), post).unwrap();
+        }
+        // editor swap and backup files aren't valid posts
+        for file in ["home-lab.md~", ".home-lab.md.swp", ".draft.md", "4913"] {
+            std::fs::write(dir.join(file), "not a post").unwrap();
+        }
+
+        let section = Section::new("test", &dir, "/test", |f: &Frontmatter| {
+            f.name.clone()
+        });
+        let posts = section.load();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut slugs = posts
+            .unwrap()
+            .into_iter()
+            .map(|p| p.metadata.slug)
+            .collect::<Vec<_>>();
+        slugs.sort();
+        assert_eq!(slugs, ["/test/_hidden", "/test/home-lab"]);
+    }
+
+    #[test]
+    fn footnote_headings_are_skipped() {
+        let anchors =
+            anchors("Text[^1]\n\n[^1]: ## Usage\n\n    ### Nested\n\n## Usage");
+        assert_eq!(anchors, [(2, "usage".to_owned())]);
+    }
+}
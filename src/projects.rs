@@ -1,142 +1,211 @@
-use std::path::PathBuf;
+use std::sync::Arc;
 
-use anyhow::{bail, Context};
 use askama::Template;
-use axum::http::StatusCode;
 use axum::{
-    extract::Path,
+    extract::{Path, State},
     response::{IntoResponse, Response},
     routing::get,
     Router,
 };
+use time::Date;
 
+use crate::config::Config;
+use crate::content::{Content, ContentStore};
+use crate::feeds::{self, Feed};
+use crate::filters;
 use crate::links::{Link, Links};
-use crate::posts::Post;
+use crate::posts::{Metadata, Post, Section};
+use crate::AppState;
+use crate::{search, tags};
 
 /// A page listing all projects.
 #[derive(Template)]
 #[template(path = "pages/projects.html")]
-struct ProjectsPage {
-    list: Vec<Post<Frontmatter, Metadata>>,
+struct ProjectsPage<'a> {
+    config: &'a Config,
+    list: Vec<&'a Post<Frontmatter, Metadata>>,
 }
 
 /// Individual project page.
 #[derive(Template)]
 #[template(path = "pages/project.html")]
-struct ProjectPage {
-    project: Post<Frontmatter, Metadata>,
-}
-
-/// Metadata about the project.
-#[derive(Debug, Clone, serde::Deserialize)]
-struct Metadata {
-    slug: String,
+struct ProjectPage<'a> {
+    config: &'a Config,
+    project: &'a Post<Frontmatter, Metadata>,
 }
 
 /// Frontmatter from the `.md` files used to generate the posts.
 #[derive(Debug, Clone, serde::Deserialize)]
-struct Frontmatter {
+pub(crate) struct Frontmatter {
     name: String,
     description: String,
-    created_at: String,
-    updated_at: Option<String>,
+    #[serde(with = "crate::dates::iso")]
+    created_at: Date,
+    #[serde(default, with = "crate::dates::iso::option")]
+    updated_at: Option<Date>,
     links: Option<Links>,
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
-#[instrument]
-async fn get_project_list() -> crate::error::Result<ProjectsPage> {
-    // search `./posts/projects` directory for markdown files
-    let projects = std::fs::read_dir("posts/projects")
-        .context("could not read the `posts/projects` directory")?
-        .filter_map(|res| res.ok()) // filter out errors (TODO: log this)
-        .map(|res| res.path())
-        .collect::<Vec<_>>();
+impl Frontmatter {
+    /// The most recent of either the created or updated date.
+    pub(crate) fn most_recent(&self) -> Date {
+        crate::dates::most_recent(self.created_at, self.updated_at)
+    }
+}
 
-    debug!("found {} project files", projects.len());
+/// Every project within the projects directory (default: `posts/projects`)
+/// sorted by most recent first.
+pub(crate) fn section(config: &Config) -> Section<Frontmatter, Date> {
+    Section::new(
+        "projects",
+        config.projects_dir(),
+        "/projects",
+        Frontmatter::most_recent,
+    )
+}
 
-    // read each file and parse into `Post`
-    // return a project page with each project
-    let mut projects = projects
+#[instrument(skip_all)]
+async fn get_project_list(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+) -> Response {
+    let content = store.get();
+    let list = content
+        .projects
         .iter()
-        .map(|path| {
-            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
-                bail!("could not get file stem");
-            };
-            Ok((path, stem))
-        })
-        .filter_map(|res| res.ok())
-        // filter out files that start with `_` since this is how we can mark
-        // hidden files
-        .filter(|(_, stem)| {
-            // if the `DISABLE_FILTER` environment variable is set to 1 then we
-            // don't filter out any files
-            if option_env!("DISABLE_FILTER").is_some_and(|v| v == "1") {
+        .filter(|p| {
+            // if the filter is disabled then we don't filter out any hidden
+            // projects
+            if config.disable_filter {
                 return true;
             }
 
-            !stem.starts_with('_')
+            !p.metadata.hidden
         })
-        .map(|(path, stem)| {
-            Post::<Frontmatter, Metadata>::from_file_with_metadata(
-                path,
-                Metadata {
-                    slug: format!("/projects/{stem}"),
-                },
-            )
-        })
-        // collect into a Vec<_> and propagate Result errors
-        .collect::<Result<Vec<_>, _>>()?;
+        .collect::<Vec<_>>();
 
-    debug!("{} projects parsed", projects.len());
+    debug!("{} projects listed", list.len());
 
-    // TODO: sort by date: either created_at or updated_at whichever is more
-    // recent
-    projects.sort_by(|a, b| {
-        b.frontmatter.created_at.cmp(&a.frontmatter.created_at)
-    });
+    let page = ProjectsPage {
+        config: &config,
+        list,
+    };
 
-    Ok(ProjectsPage { list: projects })
+    page.into_response()
 }
 
-#[instrument]
+#[instrument(skip(config, store))]
 async fn get_project_by_name(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
     Path(file): Path<String>,
-) -> crate::error::Result<Response> {
-    // get the path to the project markdown file
-    let path: PathBuf = format!("posts/projects/{}.md", file).into();
+) -> Response {
+    let content = store.get();
+    let slug = format!("/projects/{file}");
+    let Some(project) =
+        content.projects.iter().find(|p| p.metadata.slug == slug)
+    else {
+        // if the project doesn't exist then return a 404
+        info!("project not found: {}", file);
+        return crate::error::not_found_page(&config);
+    };
+
+    trace!("project found: {}", slug);
+
+    let page = ProjectPage {
+        config: &config,
+        project,
+    };
+
+    page.into_response()
+}
 
-    // if the file doesn't exist then return a 404
-    if !path.exists() {
-        info!("project file not found: {:?}", path);
-        return Ok(StatusCode::NOT_FOUND.into_response());
+/// Create a feed of all visible projects.
+fn feed<'a>(config: &'a Config, content: &'a Content) -> Feed<'a> {
+    let entries = content
+        .projects
+        .iter()
+        .filter(|p| !p.metadata.hidden)
+        .map(|p| feeds::Entry {
+            title: &p.frontmatter.name,
+            summary: &p.frontmatter.description,
+            path: &p.metadata.slug,
+            published: p.frontmatter.created_at,
+            updated: p.frontmatter.updated_at,
+            content: feeds::INCLUDE_CONTENT.then(|| p.content.to_string()),
+        })
+        .collect();
+
+    Feed {
+        base_url: &config.base_url,
+        title: format!("Projects :: {}", config.title),
+        description: "Projects that I have developed.",
+        path: "/projects",
+        entries,
     }
+}
 
-    trace!("project file: {:?}", path);
+/// Create a search document for each project.
+pub(crate) fn search_documents(
+    projects: &[Post<Frontmatter, Metadata>],
+) -> impl Iterator<Item = search::Document> + '_ {
+    projects.iter().map(|p| search::Document {
+        section: "Projects",
+        title: p.frontmatter.name.clone(),
+        summary: p.frontmatter.description.clone(),
+        path: p.metadata.slug.clone(),
+        text: p.text.clone(),
+        hidden: p.metadata.hidden,
+    })
+}
 
-    let mut project = Post::from_file_with_metadata(
-        &path,
-        Metadata {
-            slug: format!("/projects/{file}"),
-        },
-    )
-    .context("failed to create project")?;
+/// Create a tag entry for each project.
+pub(crate) fn tag_entries(
+    projects: &[Post<Frontmatter, Metadata>],
+) -> impl Iterator<Item = tags::Entry<'_>> {
+    projects.iter().map(|p| tags::Entry {
+        section: "Projects",
+        title: &p.frontmatter.name,
+        summary: Some(&p.frontmatter.description),
+        path: &p.metadata.slug,
+        created_at: p.frontmatter.created_at,
+        updated_at: p.frontmatter.updated_at,
+        tags: &p.frontmatter.tags,
+        hidden: p.metadata.hidden,
+    })
+}
 
-    // actually parse the content into HTML
-    project.parse_content()?;
+#[instrument(skip_all)]
+async fn get_rss_feed(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+) -> crate::error::Result<Response> {
+    feed(&config, &store.get()).render(feeds::Format::Rss)
+}
 
-    // make sure the content is HTML. this is a bit redundant since we just
-    // parsed the content into HTML, however, this check should remain so
-    // that the invariant doesn't get lost
-    assert!(
-        project.content.is_html(),
-        "project content must be HTML to render"
-    );
+#[instrument(skip_all)]
+async fn get_atom_feed(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+) -> crate::error::Result<Response> {
+    feed(&config, &store.get()).render(feeds::Format::Atom)
+}
 
-    Ok(ProjectPage { project }.into_response())
+#[instrument(skip_all)]
+async fn get_json_feed(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+) -> crate::error::Result<Response> {
+    feed(&config, &store.get()).render(feeds::Format::Json)
 }
 
-pub fn router() -> Router {
+pub fn router() -> Router<AppState> {
     Router::new()
         .route("/", get(get_project_list))
+        .route("/feed.xml", get(get_rss_feed))
+        .route("/atom.xml", get(get_atom_feed))
+        .route("/feed.json", get(get_json_feed))
         .route("/:file", get(get_project_by_name))
 }
@@ -1,12 +1,29 @@
+use std::path::Path;
+use std::sync::Arc;
+
 use anyhow::Context;
 use askama::Template;
-use axum::{routing::get, Router};
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
 
-const RESUME_FILE: &str = "posts/resume.ron";
+use crate::config::Config;
+use crate::content::ContentStore;
+use crate::AppState;
 
-#[derive(Debug, Clone, Template, serde::Deserialize)]
+#[derive(Template)]
 #[template(path = "pages/resume.html")]
-struct ResumePage {
+struct ResumePage<'a> {
+    config: &'a Config,
+    resume: &'a Resume,
+}
+
+/// The resume parsed from the RON file.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub(crate) struct Resume {
     education: Vec<Education>,
     skills: Vec<Skill>,
     projects: Vec<Project>,
@@ -42,21 +59,34 @@ struct Experience {
     list: Vec<String>,
 }
 
+/// Load the resume from the RON file.
 #[instrument]
-async fn get_resume() -> crate::error::Result<ResumePage> {
-    let file = std::fs::read_to_string(RESUME_FILE)
-        .context("could not open RON file: posts/resume.ron")?;
+pub(crate) fn load(path: &Path) -> anyhow::Result<Resume> {
+    let file = std::fs::read_to_string(path)
+        .with_context(|| format!("could not open RON file: {path:?}"))?;
 
-    trace!("open resume file: {RESUME_FILE}");
+    trace!("open resume file: {:?}", path);
 
-    let page = ron::from_str::<ResumePage>(&file)
-        .context(format!("could not parse RON file: {}", RESUME_FILE))?;
+    let resume = ron::from_str::<Resume>(&file)
+        .with_context(|| format!("could not parse RON file: {path:?}"))?;
 
     trace!("parse resume file");
 
-    Ok(page)
+    Ok(resume)
+}
+
+#[instrument(skip_all)]
+async fn get_resume(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+) -> Response {
+    ResumePage {
+        config: &config,
+        resume: &store.get().resume,
+    }
+    .into_response()
 }
 
-pub fn router() -> Router {
+pub fn router() -> Router<AppState> {
     Router::new().route("/", get(get_resume))
 }
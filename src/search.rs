@@ -0,0 +1,544 @@
+//! Full-text search across every post.
+//!
+//! An inverted index (term -> posts containing the term) is built whenever
+//! the content is loaded. Terms are lowercase words and matches in the title
+//! and summary of a post are worth more than matches in the text.
+//!
+//! The `/search` page contains a search box which uses htmx to request
+//! `/search/results` as the user types, which only renders the list of
+//! results. The last word of the query is treated as a prefix since the user
+//! may still be typing it.
+//!
+//! A static export has no server to search with, so its `/search` page only
+//! explains that search isn't available.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Bound;
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use crate::config::Config;
+use crate::content::ContentStore;
+use crate::AppState;
+
+/// The maximum number of results shown.
+const MAX_RESULTS: usize = 20;
+
+/// The number of words shown in a snippet.
+const SNIPPET_WORDS: usize = 30;
+
+/// How much more a match in each field is worth compared to the text.
+const TITLE_WEIGHT: f32 = 3.0;
+const SUMMARY_WEIGHT: f32 = 2.0;
+const TEXT_WEIGHT: f32 = 1.0;
+
+/// A post that can be searched.
+#[derive(Debug, Clone)]
+pub struct Document {
+    /// The name of the section the post is in (e.g. `Projects`).
+    pub section: &'static str,
+    pub title: String,
+    pub summary: String,
+    /// The path of the page containing the post.
+    pub path: String,
+    /// The plain text of the post.
+    pub text: String,
+    /// Hidden posts are only included if the filter is disabled.
+    pub hidden: bool,
+}
+
+/// An inverted index of every post.
+#[derive(Debug, Clone, Default)]
+pub struct Index {
+    documents: Vec<Document>,
+    /// Each term and the weighted number of times the term appears in each
+    /// document. This is sorted so that prefixes can be found.
+    terms: BTreeMap<String, Vec<Posting>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Posting {
+    document: usize,
+    frequency: f32,
+}
+
+/// A post that matches the query.
+#[derive(Debug, Clone)]
+pub struct Hit<'a> {
+    pub document: &'a Document,
+    pub score: f32,
+    /// A part of the text with the matching words highlighted.
+    pub snippet: Vec<Fragment<'a>>,
+}
+
+/// A part of a snippet.
+#[derive(Debug, Clone)]
+pub struct Fragment<'a> {
+    pub text: &'a str,
+    pub highlight: bool,
+}
+
+impl Index {
+    /// Create the index from every post.
+    #[instrument(skip_all)]
+    pub fn new(documents: Vec<Document>) -> Self {
+        let mut terms = BTreeMap::<String, Vec<Posting>>::new();
+
+        for (i, document) in documents.iter().enumerate() {
+            let mut frequencies = HashMap::<String, f32>::new();
+            let fields = [
+                (&document.title, TITLE_WEIGHT),
+                (&document.summary, SUMMARY_WEIGHT),
+                (&document.text, TEXT_WEIGHT),
+            ];
+            for (field, weight) in fields {
+                for term in terms_of(field) {
+                    *frequencies.entry(term).or_default() += weight;
+                }
+            }
+
+            for (term, frequency) in frequencies {
+                terms.entry(term).or_default().push(Posting {
+                    document: i,
+                    frequency,
+                });
+            }
+        }
+
+        debug!(
+            "search index created with {} terms from {} documents",
+            terms.len(),
+            documents.len()
+        );
+
+        Self { documents, terms }
+    }
+
+    /// Find the posts matching every word of the query, best match first.
+    ///
+    /// Hidden posts are only included if `include_hidden` is set.
+    pub fn search(&self, query: &str, include_hidden: bool) -> Vec<Hit<'_>> {
+        let words = terms_of(query).collect::<Vec<_>>();
+        let Some(last) = words.len().checked_sub(1) else {
+            return Vec::new();
+        };
+        // the user may still be typing the last word
+        let prefix = !query.ends_with(char::is_whitespace);
+
+        let mut scores = HashMap::<usize, f32>::new();
+        let mut matched_terms = HashSet::<&str>::new();
+
+        for (i, word) in words.iter().enumerate() {
+            let postings = if prefix && i == last {
+                self.prefixed(word)
+            } else {
+                self.terms
+                    .get_key_value(word.as_str())
+                    .into_iter()
+                    .collect()
+            };
+
+            // the score of each document for this word
+            let mut word_scores = HashMap::<usize, f32>::new();
+            for (term, postings) in postings {
+                matched_terms.insert(term);
+
+                // rare terms are worth more than common terms
+                let idf = (1.0
+                    + self.documents.len() as f32 / postings.len() as f32)
+                    .ln();
+                for posting in postings {
+                    // each extra occurrence is worth less than the last
+                    let tf = posting.frequency / (posting.frequency + 1.2);
+                    *word_scores.entry(posting.document).or_default() +=
+                        idf * tf;
+                }
+            }
+
+            // every word must match
+            if i == 0 {
+                scores = word_scores;
+            } else {
+                scores.retain(|document, score| {
+                    let Some(word_score) = word_scores.get(document) else {
+                        return false;
+                    };
+                    *score += word_score;
+                    true
+                });
+            }
+        }
+
+        let mut hits = scores
+            .into_iter()
+            .map(|(i, score)| (&self.documents[i], score))
+            .filter(|(document, _)| include_hidden || !document.hidden)
+            .map(|(document, score)| Hit {
+                document,
+                score,
+                snippet: snippet(&document.text, &matched_terms),
+            })
+            .collect::<Vec<_>>();
+
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.document.title.cmp(&b.document.title))
+        });
+        hits.truncate(MAX_RESULTS);
+
+        hits
+    }
+
+    /// Every term that starts with the prefix.
+    fn prefixed(&self, prefix: &str) -> Vec<(&String, &Vec<Posting>)> {
+        self.terms
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(term, _)| term.starts_with(prefix))
+            .collect()
+    }
+}
+
+/// Split the text into words, returning the byte range of each word.
+fn words(text: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut start = None;
+    text.char_indices()
+        .map(Some)
+        .chain(std::iter::once(None))
+        .filter_map(move |c| match (c, start) {
+            (Some((i, c)), None) if c.is_alphanumeric() => {
+                start = Some(i);
+                None
+            }
+            (Some((i, c)), Some(s)) if !c.is_alphanumeric() => {
+                start = None;
+                Some((s, i))
+            }
+            (None, Some(s)) => {
+                start = None;
+                Some((s, text.len()))
+            }
+            _ => None,
+        })
+}
+
+/// Split the text into lowercase terms.
+fn terms_of(text: &str) -> impl Iterator<Item = String> + '_ {
+    words(text).map(|(start, end)| text[start..end].to_lowercase())
+}
+
+/// Create a snippet of the text around the first matching term.
+///
+/// If none of the terms are in the text (e.g. only the title matched) then
+/// the start of the text is used.
+fn snippet<'a>(text: &'a str, terms: &HashSet<&str>) -> Vec<Fragment<'a>> {
+    let words = words(text).collect::<Vec<_>>();
+    let is_match = |&(start, end): &(usize, usize)| {
+        terms.contains(text[start..end].to_lowercase().as_str())
+    };
+
+    let first = words.iter().position(is_match).unwrap_or(0);
+    // show a few words before the match for context
+    let from = first.saturating_sub(SNIPPET_WORDS / 4);
+    let to = (from + SNIPPET_WORDS).min(words.len());
+    let Some(window) = words.get(from..to).filter(|w| !w.is_empty()) else {
+        return Vec::new();
+    };
+
+    let mut fragments = Vec::new();
+    let plain = |text: &'a str, fragments: &mut Vec<_>| {
+        if !text.is_empty() {
+            fragments.push(Fragment {
+                text,
+                highlight: false,
+            });
+        }
+    };
+
+    if from > 0 {
+        plain("… ", &mut fragments);
+    }
+
+    let mut offset = window[0].0;
+    for word in window {
+        if is_match(word) {
+            plain(&text[offset..word.0], &mut fragments);
+            fragments.push(Fragment {
+                text: &text[word.0..word.1],
+                highlight: true,
+            });
+            offset = word.1;
+        }
+    }
+    plain(&text[offset..window[window.len() - 1].1], &mut fragments);
+
+    if to < words.len() {
+        plain(" …", &mut fragments);
+    }
+
+    fragments
+}
+
+#[derive(serde::Deserialize)]
+struct SearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+/// The search page.
+#[derive(Template)]
+#[template(path = "pages/search.html")]
+struct SearchPage<'a> {
+    config: &'a Config,
+    query: &'a str,
+    hits: Vec<Hit<'a>>,
+}
+
+/// Only the list of results, used by the live search.
+#[derive(Template)]
+#[template(path = "partials/search_results.html")]
+struct SearchResults<'a> {
+    query: &'a str,
+    hits: Vec<Hit<'a>>,
+}
+
+/// The search page of a static export.
+///
+/// Searching needs the server so this only explains that search isn't
+/// available.
+#[derive(Template)]
+#[template(path = "pages/search_unavailable.html")]
+struct SearchUnavailablePage<'a> {
+    config: &'a Config,
+}
+
+/// Render the search page of a static export.
+pub(crate) fn export_page(config: &Config) -> askama::Result<String> {
+    SearchUnavailablePage { config }.render()
+}
+
+#[instrument(skip(config, store))]
+async fn get_search(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+    Query(SearchQuery { q }): Query<SearchQuery>,
+) -> Response {
+    let content = store.get();
+    let hits = content.search.search(&q, config.disable_filter);
+    debug!("{} search results", hits.len());
+
+    SearchPage {
+        config: &config,
+        query: q.trim(),
+        hits,
+    }
+    .into_response()
+}
+
+#[instrument(skip(config, store))]
+async fn get_results(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+    Query(SearchQuery { q }): Query<SearchQuery>,
+) -> Response {
+    let content = store.get();
+    let hits = content.search.search(&q, config.disable_filter);
+    debug!("{} search results", hits.len());
+
+    SearchResults {
+        query: q.trim(),
+        hits,
+    }
+    .into_response()
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_search))
+        .route("/results", get(get_results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(title: &str, summary: &str, text: &str) -> Document {
+        Document {
+            section: "Projects",
+            title: title.to_owned(),
+            summary: summary.to_owned(),
+            path: format!("/projects/{}", title.to_lowercase()),
+            text: text.to_owned(),
+            hidden: false,
+        }
+    }
+
+    fn index() -> Index {
+        Index::new(vec![
+            document("Rust", "A systems language", "Rust is fast and safe."),
+            document("Nix", "A package manager", "Nix builds packages."),
+            document("Site", "This site", "Built with Rust, htmx and Nix."),
+        ])
+    }
+
+    fn titles(hits: &[Hit<'_>]) -> Vec<String> {
+        hits.iter().map(|h| h.document.title.clone()).collect()
+    }
+
+    /// Render the snippet with `[` and `]` around the highlighted text.
+    fn render(snippet: &[Fragment<'_>]) -> String {
+        snippet
+            .iter()
+            .map(|f| {
+                if f.highlight {
+                    format!("[{}]", f.text)
+                } else {
+                    f.text.to_owned()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn terms_are_lowercase_words() {
+        let terms = terms_of("Hello, WORLD! it's 2023 — café_au-lait");
+        assert_eq!(
+            terms.collect::<Vec<_>>(),
+            ["hello", "world", "it", "s", "2023", "café", "au", "lait"]
+        );
+        assert_eq!(terms_of(" ,.! ").count(), 0);
+    }
+
+    #[test]
+    fn words_are_byte_ranges() {
+        let text = "naïve 日本語 ok";
+        let words = words(text).map(|(s, e)| &text[s..e]).collect::<Vec<_>>();
+        assert_eq!(words, ["naïve", "日本語", "ok"]);
+    }
+
+    #[test]
+    fn every_word_must_match() {
+        let index = index();
+        assert_eq!(titles(&index.search("rust ", false)), ["Rust", "Site"]);
+        assert_eq!(titles(&index.search("rust nix ", false)), ["Site"]);
+        assert!(index.search("rust python ", false).is_empty());
+        assert!(index.search("  ", false).is_empty());
+    }
+
+    #[test]
+    fn title_matches_score_higher() {
+        let index = index();
+        let hits = index.search("nix ", false);
+        assert_eq!(titles(&hits), ["Nix", "Site"]);
+        assert!(hits[0].score > hits[1].score);
+    }
+
+    #[test]
+    fn rare_terms_score_higher() {
+        let index = Index::new(vec![
+            document("A", "", "common rare"),
+            document("B", "", "common"),
+            document("C", "", "common"),
+        ]);
+        let rare = index.search("rare ", false)[0].score;
+        let common = index.search("common ", false)[0].score;
+        assert!(rare > common);
+    }
+
+    #[test]
+    fn last_word_is_a_prefix() {
+        let index = index();
+        assert_eq!(titles(&index.search("packa", false)), ["Nix"]);
+        // the prefix is complete once it is followed by a space
+        assert!(index.search("packa ", false).is_empty());
+        // only the last word is a prefix
+        assert!(index.search("packa nix", false).is_empty());
+        assert_eq!(titles(&index.search("rust ht", false)), ["Site"]);
+    }
+
+    #[test]
+    fn hidden_documents() {
+        let mut hidden = document("Hidden", "", "secret");
+        hidden.hidden = true;
+        let index = Index::new(vec![hidden]);
+
+        assert!(index.search("secret", false).is_empty());
+        assert_eq!(titles(&index.search("secret", true)), ["Hidden"]);
+    }
+
+    #[test]
+    fn snippet_highlights_matches() {
+        let index = index();
+        let hits = index.search("rust safe", false);
+        assert_eq!(render(&hits[0].snippet), "[Rust] is fast and [safe]");
+
+        // prefixed terms are highlighted in full
+        let hits = index.search("saf", false);
+        assert_eq!(render(&hits[0].snippet), "Rust is fast and [safe]");
+    }
+
+    #[test]
+    fn snippet_around_multi_byte_characters() {
+        let text = "Ünïcödé — «naïve» café 日本語 résumé";
+        let terms = HashSet::from(["café", "résumé"]);
+        assert_eq!(
+            render(&snippet(text, &terms)),
+            "Ünïcödé — «naïve» [café] 日本語 [résumé]"
+        );
+
+        // the match is found even if lowercasing changes the length
+        let terms = HashSet::from(["i̇stanbul"]);
+        assert_eq!(render(&snippet("İstanbul!", &terms)), "[İstanbul]");
+    }
+
+    #[test]
+    fn snippet_is_a_window_of_words() {
+        let text = (0..100).map(|i| format!("w{i}")).collect::<Vec<_>>();
+        let text = text.join(" ");
+
+        let rendered = render(&snippet(&text, &HashSet::from(["w50"])));
+        let from = 50 - SNIPPET_WORDS / 4;
+        assert!(rendered.starts_with(&format!("… w{from} ")));
+        assert!(rendered.contains(" [w50] "));
+        assert!(
+            rendered.ends_with(&format!(" w{} …", from + SNIPPET_WORDS - 1))
+        );
+
+        // the start of the text is used if nothing matches
+        let rendered = render(&snippet(&text, &HashSet::new()));
+        assert!(rendered.starts_with("w0 w1 "));
+        assert!(render(&snippet("", &HashSet::new())).is_empty());
+    }
+
+    #[test]
+    fn snippet_is_escaped() {
+        let index = Index::new(vec![document(
+            "<b>Title</b>",
+            "",
+            "use <script>alert(1)</script> & <b>bold</b>",
+        )]);
+        let hits = index.search("script", false);
+        let html = SearchResults {
+            query: "script",
+            hits,
+        }
+        .render()
+        .unwrap();
+
+        assert!(html.contains(">&lt;b&gt;Title&lt;/b&gt;</a"), "{html}");
+        assert!(html.contains(
+            "use &lt;<mark class=\"bg-dark-yellow text-dark-bg0\">script</mark>\
+             &gt;alert"
+        ));
+        // the snippet ends at the end of the last word
+        assert!(html.contains("&amp; &lt;b&gt;bold&lt;/b</p>"));
+        assert!(!html.contains("<script>"));
+    }
+}
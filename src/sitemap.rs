@@ -0,0 +1,138 @@
+//! Sitemap and robots.txt.
+//!
+//! The sitemap lists every public page, every visible project, every published
+//! blog post and every tag so that search engines can find all of the content.
+//! Hidden projects (file name starts with `_`) and drafts are never included.
+//!
+//! Specifications:
+//! - Sitemap: https://www.sitemaps.org/protocol.html
+//! - robots.txt: https://www.rfc-editor.org/rfc/rfc9309
+
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use time::Date;
+
+use crate::config::Config;
+use crate::content::{Content, ContentStore};
+use crate::AppState;
+
+#[derive(Template)]
+#[template(path = "sitemap.xml")]
+struct Sitemap<'a> {
+    base_url: &'a str,
+    urls: Vec<Url>,
+}
+
+/// A single URL within the sitemap.
+pub(crate) struct Url {
+    /// The path of the page (e.g. `/projects`).
+    pub(crate) path: String,
+    /// When the page was last modified.
+    pub(crate) last_modified: Option<Date>,
+}
+
+#[derive(Template)]
+#[template(path = "robots.txt")]
+struct Robots<'a> {
+    base_url: &'a str,
+    /// Paths that crawlers are not allowed to visit.
+    disallow: &'a [String],
+}
+
+#[instrument(skip_all)]
+async fn get_sitemap(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+) -> crate::error::Result<Response> {
+    let urls = urls(&store.get());
+    debug!("{} urls in sitemap", urls.len());
+
+    let body = Sitemap {
+        base_url: &config.base_url,
+        urls,
+    }
+    .render()?;
+    Ok(([(header::CONTENT_TYPE, "application/xml")], body).into_response())
+}
+
+/// Every URL listed in the sitemap.
+pub(crate) fn urls(content: &Content) -> Vec<Url> {
+    let projects = content
+        .projects
+        .iter()
+        .filter(|p| !p.metadata.hidden)
+        .map(|p| Url {
+            path: p.metadata.slug.clone(),
+            last_modified: Some(p.frontmatter.most_recent()),
+        })
+        .collect::<Vec<_>>();
+
+    let blog = crate::blog::published(&content.blog)
+        .map(|p| Url {
+            path: p.metadata.slug.clone(),
+            last_modified: Some(p.frontmatter.most_recent()),
+        })
+        .collect::<Vec<_>>();
+
+    let entries = crate::tags::entries(content, false);
+    let tags = crate::tags::tags(&entries);
+
+    let static_pages = [
+        ("/", None),
+        ("/resume", None),
+        // list pages are modified whenever any of the posts are modified
+        (
+            "/projects",
+            projects.iter().filter_map(|u| u.last_modified).max(),
+        ),
+        (
+            "/wishlist",
+            content
+                .wishlist
+                .iter()
+                .map(|p| p.frontmatter.most_recent())
+                .max(),
+        ),
+        ("/blog", blog.iter().filter_map(|u| u.last_modified).max()),
+        ("/tags", tags.iter().map(|t| t.last_modified).max()),
+    ]
+    .into_iter()
+    .map(|(path, last_modified)| Url {
+        path: path.to_owned(),
+        last_modified,
+    });
+
+    let tag_pages = tags.iter().map(|t| Url {
+        path: format!("/tags/{}", t.slug),
+        last_modified: Some(t.last_modified),
+    });
+
+    static_pages
+        .chain(projects)
+        .chain(blog)
+        .chain(tag_pages)
+        .collect()
+}
+
+#[instrument(skip_all)]
+async fn get_robots(State(config): State<Arc<Config>>) -> Response {
+    Robots {
+        base_url: &config.base_url,
+        disallow: &config.disallow,
+    }
+    .into_response()
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/sitemap.xml", get(get_sitemap))
+        .route("/robots.txt", get(get_robots))
+}
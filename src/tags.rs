@@ -0,0 +1,213 @@
+//! Tags which group posts across every section (e.g. all `Rust` posts).
+//!
+//! Tags are set in the frontmatter of each post. The `/tags` page lists every
+//! tag and `/tags/:tag` lists every post with the tag. Tags are matched using
+//! their slug so `Home Lab` and `home-lab` are the same tag.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use time::Date;
+
+use crate::config::Config;
+use crate::content::{Content, ContentStore};
+use crate::filters;
+use crate::posts::slug;
+use crate::{blog, projects, wishlist, AppState};
+
+/// A post with at least one tag.
+#[derive(Debug, Clone)]
+pub struct Entry<'a> {
+    /// The name of the section the post is in (e.g. `Projects`).
+    pub section: &'static str,
+    pub title: &'a str,
+    /// A short description of the post (if the post has one).
+    pub summary: Option<&'a str>,
+    /// The path of the page containing the post.
+    pub path: &'a str,
+    pub created_at: Date,
+    pub updated_at: Option<Date>,
+    pub tags: &'a [String],
+    /// Hidden posts are only included if the filter is disabled.
+    pub hidden: bool,
+}
+
+impl Entry<'_> {
+    /// The most recent of either the created or updated date.
+    fn most_recent(&self) -> Date {
+        crate::dates::most_recent(self.created_at, self.updated_at)
+    }
+
+    fn has_tag(&self, slug: &str) -> bool {
+        self.tags.iter().any(|t| self::slug(t) == slug)
+    }
+}
+
+/// A tag and the number of posts with the tag.
+#[derive(Debug, Clone)]
+pub struct Tag<'a> {
+    /// The name of the tag as written in the first post that uses it.
+    pub name: &'a str,
+    pub slug: String,
+    pub count: usize,
+    /// The most recent date any post with the tag was modified.
+    pub last_modified: Date,
+}
+
+/// Every post with at least one tag, most recent first.
+///
+/// Hidden posts are only included if `include_hidden` is set.
+pub fn entries(content: &Content, include_hidden: bool) -> Vec<Entry<'_>> {
+    let mut entries = projects::tag_entries(&content.projects)
+        .chain(wishlist::tag_entries(&content.wishlist))
+        .chain(blog::tag_entries(&content.blog))
+        .filter(|e| !e.tags.is_empty())
+        .filter(|e| include_hidden || !e.hidden)
+        .collect::<Vec<_>>();
+
+    entries.sort_by_key(|e| std::cmp::Reverse(e.most_recent()));
+
+    entries
+}
+
+/// Every tag used by the entries, sorted by name.
+pub fn tags<'a>(entries: &[Entry<'a>]) -> Vec<Tag<'a>> {
+    let mut tags = BTreeMap::<String, Tag<'a>>::new();
+
+    for entry in entries {
+        for name in entry.tags {
+            let slug = slug(name);
+            if slug.is_empty() {
+                continue;
+            }
+
+            let tag = tags.entry(slug.clone()).or_insert_with(|| Tag {
+                name,
+                slug,
+                count: 0,
+                last_modified: entry.most_recent(),
+            });
+            tag.count += 1;
+            tag.last_modified = tag.last_modified.max(entry.most_recent());
+        }
+    }
+
+    tags.into_values().collect()
+}
+
+/// A page listing every tag.
+#[derive(Template)]
+#[template(path = "pages/tags.html")]
+struct TagsPage<'a> {
+    config: &'a Config,
+    tags: Vec<Tag<'a>>,
+}
+
+/// A page listing every post with a tag.
+#[derive(Template)]
+#[template(path = "pages/tag.html")]
+struct TagPage<'a> {
+    config: &'a Config,
+    tag: &'a Tag<'a>,
+    entries: Vec<&'a Entry<'a>>,
+}
+
+#[instrument(skip_all)]
+async fn get_tags(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+) -> Response {
+    let content = store.get();
+    let entries = entries(&content, config.disable_filter);
+    let tags = tags(&entries);
+
+    debug!("{} tags listed", tags.len());
+
+    TagsPage {
+        config: &config,
+        tags,
+    }
+    .into_response()
+}
+
+#[instrument(skip(config, store))]
+async fn get_tag(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+    Path(tag): Path<String>,
+) -> Response {
+    let content = store.get();
+    let entries = entries(&content, config.disable_filter);
+    let tags = tags(&entries);
+
+    let Some(tag) = tags.iter().find(|t| t.slug == tag) else {
+        // if no posts have the tag then return a 404
+        info!("tag not found: {}", tag);
+        return crate::error::not_found_page(&config);
+    };
+
+    let page = TagPage {
+        config: &config,
+        tag,
+        entries: entries.iter().filter(|e| e.has_tag(&tag.slug)).collect(),
+    };
+
+    page.into_response()
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_tags))
+        .route("/:tag", get(get_tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry<'a>(tags: &'a [String]) -> Entry<'a> {
+        Entry {
+            section: "Projects",
+            title: "Title",
+            summary: None,
+            path: "/projects/title",
+            created_at: time::macros::date!(2023 - 10 - 29),
+            updated_at: None,
+            tags,
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn tags_are_matched_by_slug() {
+        let first = ["Home Lab".to_owned(), "Rust".to_owned()];
+        let second = ["home-lab".to_owned(), "?".to_owned()];
+        let entries = [entry(&first), entry(&second)];
+        let tags = tags(&entries);
+
+        let tags = tags
+            .iter()
+            .map(|t| (t.name, t.slug.as_str(), t.count))
+            .collect::<Vec<_>>();
+        assert_eq!(tags, [("Home Lab", "home-lab", 2), ("Rust", "rust", 1)]);
+        assert!(entries[1].has_tag("home-lab"));
+    }
+
+    #[test]
+    fn similar_tags_are_separate() {
+        let names = ["C++".to_owned(), "C#".to_owned(), "C".to_owned()];
+        let entries = [entry(&names)];
+        let slugs = tags(&entries)
+            .into_iter()
+            .map(|t| t.slug)
+            .collect::<Vec<_>>();
+        assert_eq!(slugs, ["c", "c-plus-plus", "c-sharp"]);
+    }
+}
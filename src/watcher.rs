@@ -0,0 +1,134 @@
+//! Filesystem watcher used to hot reload the content.
+//!
+//! The projects, wishlist and blog directories and the resume file within the
+//! content directory (default: `posts/`) are watched for changes. When a
+//! change is detected the entire `ContentStore` is reloaded.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+
+use crate::content::ContentStore;
+
+/// How long to wait for more events before reloading the content.
+///
+/// Editors will often write a file in multiple steps (e.g. write to a swap
+/// file then rename) so we want to group these events together.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Start watching the content files for changes.
+///
+/// The returned `Debouncer` must be kept alive for as long as the content
+/// should be watched, dropping it will stop the watcher.
+#[instrument(skip_all)]
+pub fn watch(
+    store: ContentStore,
+) -> anyhow::Result<Debouncer<RecommendedWatcher>> {
+    let config = store.config();
+
+    // the watcher reports absolute paths so use the absolute versions of the
+    // watched paths
+    let cwd = std::env::current_dir()?;
+    let dirs = [
+        config.projects_dir(),
+        config.wishlist_dir(),
+        config.blog_dir(),
+    ]
+    .map(|d| cwd.join(d));
+    let files = [config.resume_file()].map(|f| cwd.join(f));
+    let watched = Watched {
+        dirs: dirs.to_vec(),
+        files: files.to_vec(),
+    };
+
+    let mut debouncer =
+        new_debouncer(DEBOUNCE, move |res: DebounceEventResult| match res {
+            Ok(events) => {
+                // only reload if one of the changed paths is actually content
+                let relevant = events.iter().any(|e| watched.contains(&e.path));
+                if !relevant {
+                    return;
+                }
+
+                debug!("content changed: {:?}", events);
+
+                // log the error but keep serving the old content
+                if let Err(e) = store.reload() {
+                    error!("failed to reload content: {:?}", e);
+                }
+            }
+            Err(e) => error!("content watcher error: {:?}", e),
+        })
+        .context("could not create the content watcher")?;
+
+    // watch the parent directory of each file rather than the file itself
+    // since editors often replace the file which would remove the watch
+    let parents = files
+        .iter()
+        .filter_map(|f| f.parent())
+        .map(|p| (p, RecursiveMode::NonRecursive));
+    let dirs = dirs.iter().map(|d| (d.as_path(), RecursiveMode::Recursive));
+
+    for (path, mode) in dirs.chain(parents) {
+        debouncer
+            .watcher()
+            .watch(path, mode)
+            .with_context(|| format!("could not watch {path:?}"))?;
+        trace!("watching {:?}", path);
+    }
+
+    info!("watching content for changes");
+
+    Ok(debouncer)
+}
+
+/// The absolute paths of the content being watched.
+struct Watched {
+    /// Directories containing posts.
+    dirs: Vec<PathBuf>,
+    /// Individual files (e.g. the resume).
+    files: Vec<PathBuf>,
+}
+
+impl Watched {
+    /// Check if the given path is content that is being watched.
+    ///
+    /// Only posts within the directories are content, any other file (e.g.
+    /// the swap files and backups written by editors) is ignored.
+    fn contains(&self, path: &Path) -> bool {
+        let in_dir = self.dirs.iter().any(|d| path.starts_with(d));
+        (in_dir && crate::posts::is_post(path))
+            || self.files.iter().any(|f| path == f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_content_is_watched() {
+        let watched = Watched {
+            dirs: vec![PathBuf::from("/posts/projects")],
+            files: vec![PathBuf::from("/posts/resume.ron")],
+        };
+
+        assert!(watched.contains(Path::new("/posts/projects/home-lab.md")));
+        assert!(watched.contains(Path::new("/posts/resume.ron")));
+
+        for path in [
+            "/posts/projects/home-lab.md~",
+            "/posts/projects/.home-lab.md.swp",
+            "/posts/projects/4913",
+            "/posts/projects/notes.txt",
+            "/posts/resume.ron~",
+            "/posts/.resume.ron.swp",
+            "/posts/blog/post.md",
+        ] {
+            assert!(!watched.contains(Path::new(path)), "{path:?}");
+        }
+    }
+}
@@ -1,95 +1,107 @@
-use anyhow::{bail, Context};
+use std::sync::Arc;
+
 use askama::Template;
 use axum::{
+    extract::State,
     response::{IntoResponse, Response},
     routing::get,
     Router,
 };
+use time::Date;
 
-use crate::posts::Post;
+use crate::config::Config;
+use crate::content::ContentStore;
+use crate::filters;
+use crate::posts::{Metadata, Post, Section};
+use crate::AppState;
+use crate::{search, tags};
 
 #[derive(Template)]
 #[template(path = "pages/wishlist.html")]
-struct WishlistPage {
-    list: Vec<Post<Frontmatter, ()>>,
+struct WishlistPage<'a> {
+    config: &'a Config,
+    list: &'a [Post<Frontmatter, Metadata>],
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
-struct Frontmatter {
+pub(crate) struct Frontmatter {
     name: String,
-    created_at: String,
-    updated_at: Option<String>,
+    #[serde(with = "crate::dates::iso")]
+    created_at: Date,
+    #[serde(default, with = "crate::dates::iso::option")]
+    updated_at: Option<Date>,
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
-#[instrument]
-async fn get_wishlist() -> crate::error::Result<Response> {
-    // search `./posts/wishlist` directory for markdown files
-    let list = std::fs::read_dir("posts/wishlist")
-        .context("could not read the `posts/wishlist` directory")?
-        .filter_map(|res| {
-            // filter out and log errors
-            if let Err(e) = res {
-                warn!("could not read file: {}", e);
-                return None;
-            }
-
-            res.ok()
-        })
-        .map(|res| res.path())
-        .collect::<Vec<_>>();
-
-    debug!("found {} wishlist project files", list.len());
-
-    // read each file and parse into a `Post`
-    let mut list = list
-        .iter()
-        .map(|path| {
-            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
-                bail!("could not get file stem");
-            };
-            Ok((path, stem))
-        })
-        .filter_map(|res| res.ok())
-        // filter out files that start with `_` since this is how we can mark
-        // hidden files
-        .filter(|(_, stem)| {
-            // if the `DISABLE_FILTER` environment variable is set to 1 then we
-            // don't filter out any files
-            if option_env!("DISABLE_FILTER").is_some_and(|v| v == "1") {
-                return true;
-            }
+impl Frontmatter {
+    /// The most recent of either the created or updated date.
+    pub(crate) fn most_recent(&self) -> Date {
+        crate::dates::most_recent(self.created_at, self.updated_at)
+    }
+}
 
-            !stem.starts_with('_')
-        })
-        .map(|(path, _)| Post::<Frontmatter, ()>::from_file(path))
-        // collect into a Vec<_> and propagate Result errors
-        .collect::<Result<Vec<_>, _>>()?;
+/// Every wishlist post within the wishlist directory (default:
+/// `posts/wishlist`) sorted by most recent first.
+///
+/// Hidden posts are only loaded if the filter is disabled. Every post is
+/// rendered on the same page so the `id`s within each post are prefixed.
+pub(crate) fn section(config: &Config) -> Section<Frontmatter, Date> {
+    Section::new(
+        "wishlist",
+        config.wishlist_dir(),
+        "/wishlist",
+        Frontmatter::most_recent,
+    )
+    .include_hidden(config.disable_filter)
+    .prefix_ids(true)
+}
 
-    debug!("{} wishlist projects parsed", list.len());
+/// Create a search document for each wishlist post.
+pub(crate) fn search_documents(
+    list: &[Post<Frontmatter, Metadata>],
+) -> impl Iterator<Item = search::Document> + '_ {
+    list.iter().map(|p| search::Document {
+        section: "Wishlist",
+        title: p.frontmatter.name.clone(),
+        summary: String::new(),
+        path: "/wishlist".to_owned(),
+        text: p.text.clone(),
+        hidden: p.metadata.hidden,
+    })
+}
 
-    // TODO: sort by date: either created_at or updated_at whichever is more
-    // recent
-    list.sort_by(|a, b| {
-        b.frontmatter.created_at.cmp(&a.frontmatter.created_at)
-    });
+/// Create a tag entry for each wishlist post.
+pub(crate) fn tag_entries(
+    list: &[Post<Frontmatter, Metadata>],
+) -> impl Iterator<Item = tags::Entry<'_>> {
+    list.iter().map(|p| tags::Entry {
+        section: "Wishlist",
+        title: &p.frontmatter.name,
+        summary: None,
+        path: "/wishlist",
+        created_at: p.frontmatter.created_at,
+        updated_at: p.frontmatter.updated_at,
+        tags: &p.frontmatter.tags,
+        hidden: p.metadata.hidden,
+    })
+}
 
-    // parse the content into HTML for each post
-    for post in list.iter_mut() {
-        post.parse_content()
-            .context("failed to parse wishlist post")?;
-    }
+#[instrument(skip_all)]
+async fn get_wishlist(
+    State(config): State<Arc<Config>>,
+    State(store): State<ContentStore>,
+) -> Response {
+    let content = store.get();
 
-    // make sure the content is HTML. this is a bit redundant since we just
-    // parsed the content into HTML, however, this check should remain so
-    // that the invariant doesn't get lost
-    assert!(
-        list.iter().all(|p| p.content.is_html()),
-        "all wishlist files must be parsed into HTML before render"
-    );
+    let page = WishlistPage {
+        config: &config,
+        list: &content.wishlist,
+    };
 
-    Ok(WishlistPage { list }.into_response())
+    page.into_response()
 }
 
-pub fn router() -> Router {
+pub fn router() -> Router<AppState> {
     Router::new().route("/", get(get_wishlist))
 }